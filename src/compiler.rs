@@ -0,0 +1,834 @@
+pub mod code;
+mod compiler_test;
+pub mod disasm;
+pub mod error;
+pub mod symbol_table;
+
+use std::rc::Rc;
+
+use code::{Instructions, Opcode, SLICE_END, SLICE_START, SpanTable, make, stack_use};
+use error::CompileError;
+use symbol_table::{Symbol, SymbolScope, SymbolTable};
+
+use crate::{
+    lexer::token::{Span, TokenKind},
+    object::{Capture, CompiledFunction, Object},
+    parser::ast::{
+        AssignExp, BlockStatement, Expression, For, FunctionLiteral, Ident, IfExp, InfixExp,
+        Literal, Program, Statement, While,
+    },
+};
+
+/// Operand of the jumps emitted before their target is known
+const PLACEHOLDER: usize = u16::MAX as usize;
+
+/// Output of the compiler, the main function runs the program and the constants are shared by
+/// every function
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bytecode {
+    pub main: Rc<CompiledFunction>,
+    pub constants: Vec<Object>,
+    /// Names of the globals in the order of their slots, the ones that are not set are looked up
+    /// in the builtins
+    pub globals: Vec<String>,
+}
+
+/// Instructions of the function being compiled
+#[derive(Debug, Default)]
+struct CompilationScope {
+    instructions: Instructions,
+    spans: SpanTable,
+    loops: Vec<Loop>,
+    /// Number of values the instructions emitted so far leave on the stack
+    depth: isize,
+}
+
+/// A loop whose body is being compiled
+#[derive(Debug)]
+struct Loop {
+    /// Where continue jumps to
+    start: usize,
+    /// Stack depth at the start of the body, break and continue pop what is above it
+    depth: isize,
+    /// Jumps to patch with the end of the loop once it is known
+    breaks: Vec<usize>,
+}
+
+/// Lowers a program to bytecode for the vm, running it gives the same results as the evaluator
+///
+/// The functions and the bodies of the for loops are scopes like in the evaluator, their let
+/// statements are defined when they are entered so closures can refer to the ones that follow,
+/// and a name that may refer to several variables tries them from the innermost one until one is
+/// set
+pub struct Compiler {
+    constants: Vec<Object>,
+    symbols: SymbolTable,
+    scopes: Vec<CompilationScope>,
+}
+
+impl Default for Compiler {
+    fn default() -> Self {
+        Self {
+            constants: vec![],
+            symbols: SymbolTable::new(),
+            scopes: vec![CompilationScope::default()],
+        }
+    }
+}
+
+impl Compiler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Compiles the program into its main function, like the evaluator its value is the value of
+    /// the last statement or of the first top level return
+    pub fn compile(mut self, program: &Program) -> Result<Bytecode, CompileError> {
+        let span = Span {
+            start: 0,
+            end: program.statements.last().map_or(0, |s| s.span().end),
+        };
+        self.compile_block_value(&program.statements, &span)?;
+        self.emit(Opcode::ReturnValue, &[], &span);
+
+        let scope = self.scopes.pop().expect("The main scope is never left");
+        Ok(Bytecode {
+            main: Rc::new(CompiledFunction {
+                instructions: scope.instructions,
+                spans: scope.spans,
+                // The variables of the for loops of the program
+                num_locals: self.symbols.num_locals,
+                num_parameters: 0,
+                captures: vec![],
+            }),
+            constants: self.constants,
+            globals: self.symbols.global_names(),
+        })
+    }
+
+    fn compile_statement(&mut self, stmt: &Statement) -> Result<(), CompileError> {
+        match stmt {
+            Statement::Expression(exp) => {
+                self.compile_expression(exp)?;
+                self.emit(Opcode::Pop, &[], exp.span());
+            }
+            Statement::Let(let_stmt) => {
+                let TokenKind::Ident { name } = &let_stmt.identifier.kind else {
+                    unreachable!("Cannot have let without identifier")
+                };
+
+                self.compile_expression(&let_stmt.exp)?;
+
+                let symbol = self.define(name, &let_stmt.span)?;
+                self.symbols.mark_set(name);
+                let op = match symbol.scope {
+                    SymbolScope::Global => Opcode::SetGlobal,
+                    _ => Opcode::SetLocal,
+                };
+                self.emit(op, &[symbol.index], &let_stmt.span);
+            }
+            Statement::Return(ret) => {
+                match &ret.exp {
+                    Some(exp) => self.compile_expression(exp)?,
+                    None => {
+                        self.emit(Opcode::Null, &[], &ret.span);
+                    }
+                }
+                self.emit(Opcode::ReturnValue, &[], &ret.span);
+            }
+            Statement::While(while_stmt) => self.compile_while(while_stmt)?,
+            Statement::For(for_stmt) => self.compile_for(for_stmt)?,
+            Statement::Break(span) => self.compile_loop_control("break", span)?,
+            Statement::Continue(span) => self.compile_loop_control("continue", span)?,
+            Statement::Error(span) => {
+                return Err(CompileError::SyntaxError { span: span.clone() });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Compiles statements leaving the value of the last one on the stack, statements other than
+    /// expressions have no value so null is left instead
+    fn compile_block_value(
+        &mut self,
+        statements: &[Statement],
+        span: &Span,
+    ) -> Result<(), CompileError> {
+        match statements.split_last() {
+            Some((Statement::Expression(exp), rest)) => {
+                for stmt in rest {
+                    self.compile_statement(stmt)?;
+                }
+                self.compile_expression(exp)
+            }
+            _ => {
+                for stmt in statements {
+                    self.compile_statement(stmt)?;
+                }
+                self.emit(Opcode::Null, &[], span);
+                Ok(())
+            }
+        }
+    }
+
+    fn compile_while(&mut self, while_stmt: &While) -> Result<(), CompileError> {
+        let span = &while_stmt.span;
+
+        let start = self.jump_target(span)?;
+        self.compile_expression(&while_stmt.cond)?;
+        let exit = self.emit(Opcode::JumpNotTruthy, &[PLACEHOLDER], span);
+
+        self.symbols.enter_branch();
+        let breaks = self.compile_loop_body(&while_stmt.body, start)?;
+        self.symbols.leave_branch();
+        self.emit(Opcode::Jump, &[start], span);
+
+        let end = self.jump_target(span)?;
+        for jump in breaks.into_iter().chain([exit]) {
+            self.patch_jump(jump, end);
+        }
+        Ok(())
+    }
+
+    /// The iterable and the position of the next item stay on the stack while the loop runs, each
+    /// iteration gets new variables for the loop variable and the lets of the body
+    fn compile_for(&mut self, for_stmt: &For) -> Result<(), CompileError> {
+        let span = &for_stmt.span;
+
+        self.compile_expression(&for_stmt.iterable)?;
+        self.emit(Opcode::IterStart, &[], span);
+
+        self.symbols = SymbolTable::new_loop(std::mem::take(&mut self.symbols));
+        let variable = self.define(&for_stmt.variable.name, &for_stmt.variable.span)?;
+        self.symbols.mark_set(&variable.name);
+        self.declare(&for_stmt.body.statements)?;
+        let locals = self.symbols.loop_locals();
+
+        let start = self.jump_target(span)?;
+        let exit = self.emit(Opcode::IterNext, &[PLACEHOLDER], span);
+        self.emit(Opcode::NewLocals, &[locals.start, locals.end - 1], span);
+        self.emit(Opcode::SetLocal, &[variable.index], &for_stmt.variable.span);
+
+        let breaks = self.compile_loop_body(&for_stmt.body, start)?;
+        self.emit(Opcode::Jump, &[start], span);
+
+        let end = self.jump_target(span)?;
+        for jump in breaks.into_iter().chain([exit]) {
+            self.patch_jump(jump, end);
+        }
+        let symbols = std::mem::take(&mut self.symbols);
+        self.symbols = symbols.into_outer().expect("The table was enclosed above");
+
+        self.emit(Opcode::Pop, &[], span);
+        self.emit(Opcode::Pop, &[], span);
+        Ok(())
+    }
+
+    /// Compiles the body of a loop starting at the given offset, returns the jumps of its breaks
+    fn compile_loop_body(
+        &mut self,
+        body: &BlockStatement,
+        start: usize,
+    ) -> Result<Vec<usize>, CompileError> {
+        let depth = self.scope().depth;
+        self.scope_mut().loops.push(Loop {
+            start,
+            depth,
+            breaks: vec![],
+        });
+
+        for stmt in body.statements.iter() {
+            self.compile_statement(stmt)?;
+        }
+
+        let current = self
+            .scope_mut()
+            .loops
+            .pop()
+            .expect("The loop was pushed above");
+        Ok(current.breaks)
+    }
+
+    /// Jumps to the end of the innermost loop for a break, or to its next iteration for a continue,
+    /// like the evaluator it is an error when it runs outside of a loop
+    fn compile_loop_control(
+        &mut self,
+        keyword: &'static str,
+        span: &Span,
+    ) -> Result<(), CompileError> {
+        let scope = self.scope();
+        let Some(current) = scope.loops.last() else {
+            let message = Object::String(format!("{keyword} outside of a loop"));
+            let index = self.add_constant(message, span)?;
+            self.emit(Opcode::Fail, &[index], span);
+            return Ok(());
+        };
+        let (start, loop_depth, depth) = (current.start, current.depth, scope.depth);
+
+        // Values of the enclosing expressions, like the 1 in `1 + if (x) { break; }`
+        for _ in loop_depth..depth {
+            self.emit(Opcode::Pop, &[], span);
+        }
+
+        if keyword == "break" {
+            let jump = self.emit(Opcode::Jump, &[PLACEHOLDER], span);
+            let current = self.scope_mut().loops.last_mut();
+            current.expect("Checked above").breaks.push(jump);
+        } else {
+            self.emit(Opcode::Jump, &[start], span);
+        }
+
+        // What follows never runs, keep counting as if the values were still there so the
+        // enclosing expressions stay balanced
+        self.scope_mut().depth = depth;
+        Ok(())
+    }
+
+    fn compile_expression(&mut self, exp: &Expression) -> Result<(), CompileError> {
+        let span = exp.span();
+
+        match exp {
+            Expression::Literal(Literal::Integer(int)) => {
+                self.emit_constant(Object::Integer(int.value), span)?
+            }
+            Expression::Literal(Literal::Float(float)) => {
+                self.emit_constant(Object::Float(float.value), span)?
+            }
+            Expression::Literal(Literal::String(string)) => {
+                self.emit_constant(Object::String(string.value.clone()), span)?
+            }
+            Expression::Literal(Literal::Boolean(boolean)) => {
+                let op = if boolean.value {
+                    Opcode::True
+                } else {
+                    Opcode::False
+                };
+                self.emit(op, &[], span);
+            }
+            Expression::Identifier(ident) => {
+                let symbols = self.symbols.resolve(&ident.name);
+                self.emit_load(&symbols, Opcode::GetGlobal, span)?;
+            }
+            Expression::Prefix(prefix) => {
+                self.compile_expression(&prefix.right)?;
+                let op = match prefix.operator.kind {
+                    TokenKind::Minus => Opcode::Minus,
+                    TokenKind::Bang => Opcode::Bang,
+                    TokenKind::Tilde => Opcode::BitNot,
+                    _ => return Err(unknown_operator(&prefix.operator.kind, span)),
+                };
+                self.emit(op, &[], span);
+            }
+            Expression::Infix(infix) => {
+                if matches!(infix.operator.kind, TokenKind::And | TokenKind::Or) {
+                    return self.compile_logical(infix);
+                }
+                self.compile_expression(&infix.left)?;
+                self.compile_expression(&infix.right)?;
+                let op = binary_opcode(&infix.operator.kind, span)?;
+                self.emit(op, &[], span);
+            }
+            Expression::If(if_exp) => self.compile_if(if_exp)?,
+            Expression::Function(function) => self.compile_function(function)?,
+            Expression::Call(call) => {
+                self.compile_expression(&call.function)?;
+                for arg in call.arguments.iter() {
+                    self.compile_expression(arg)?;
+                }
+                check_count(call.arguments.len(), "arguments", u8::MAX as usize, span)?;
+                self.emit(Opcode::Call, &[call.arguments.len()], span);
+            }
+            Expression::Array(array) => {
+                for element in array.elements.iter() {
+                    self.compile_expression(element)?;
+                }
+                check_count(array.elements.len(), "elements", u16::MAX as usize, span)?;
+                self.emit(Opcode::Array, &[array.elements.len()], span);
+            }
+            Expression::Hash(hash) => {
+                for (key, value) in hash.pairs.iter() {
+                    self.compile_expression(key)?;
+                    self.compile_expression(value)?;
+                }
+                check_count(hash.pairs.len(), "pairs", u16::MAX as usize, span)?;
+                self.emit(Opcode::Hash, &[hash.pairs.len()], span);
+            }
+            Expression::Index(index_exp) => {
+                self.compile_expression(&index_exp.left)?;
+                self.compile_expression(&index_exp.index)?;
+                self.emit(Opcode::Index, &[], span);
+            }
+            Expression::Slice(slice) => {
+                // Each operand is checked before the next one is evaluated
+                self.compile_expression(&slice.left)?;
+                self.emit(Opcode::CheckSliceable, &[], span);
+                // Only the bounds that are there are pushed, the flags tell which ones
+                let mut flags = 0;
+                for (bound, flag) in [(&slice.start, SLICE_START), (&slice.end, SLICE_END)] {
+                    if let Some(bound) = bound {
+                        self.compile_expression(bound)?;
+                        self.emit(Opcode::CheckSliceBound, &[], span);
+                        flags |= flag;
+                    }
+                }
+                self.emit(Opcode::Slice, &[flags], span);
+            }
+            Expression::Range(range) => {
+                self.compile_expression(&range.start)?;
+                self.emit(Opcode::CheckRangeBound, &[], span);
+                self.compile_expression(&range.end)?;
+                self.emit(Opcode::Range, &[], span);
+            }
+            Expression::Assign(assign) => self.compile_assign(assign)?,
+        }
+
+        Ok(())
+    }
+
+    /// `&&` and `||` only run their right operand when the left one does not decide the result
+    fn compile_logical(&mut self, infix: &InfixExp) -> Result<(), CompileError> {
+        let span = &infix.span;
+        let mut to_end = vec![];
+
+        self.compile_expression(&infix.left)?;
+        let left_falsy = self.emit(Opcode::JumpNotTruthy, &[PLACEHOLDER], span);
+        let is_or = infix.operator.kind == TokenKind::Or;
+        if is_or {
+            self.emit(Opcode::True, &[], span);
+            to_end.push(self.emit(Opcode::Jump, &[PLACEHOLDER], span));
+            let right = self.jump_target(span)?;
+            self.patch_jump(left_falsy, right);
+            // Only one of the results is pushed
+            self.scope_mut().depth -= 1;
+        }
+
+        self.compile_expression(&infix.right)?;
+        let right_falsy = self.emit(Opcode::JumpNotTruthy, &[PLACEHOLDER], span);
+        self.emit(Opcode::True, &[], span);
+        to_end.push(self.emit(Opcode::Jump, &[PLACEHOLDER], span));
+
+        let falsy = self.jump_target(span)?;
+        self.patch_jump(right_falsy, falsy);
+        if !is_or {
+            self.patch_jump(left_falsy, falsy);
+        }
+        self.emit(Opcode::False, &[], span);
+        self.scope_mut().depth -= 1;
+
+        let end = self.jump_target(span)?;
+        for jump in to_end {
+            self.patch_jump(jump, end);
+        }
+        Ok(())
+    }
+
+    fn compile_if(&mut self, if_exp: &IfExp) -> Result<(), CompileError> {
+        let span = &if_exp.span;
+
+        self.compile_expression(&if_exp.cond)?;
+        let to_alternative = self.emit(Opcode::JumpNotTruthy, &[PLACEHOLDER], span);
+
+        self.symbols.enter_branch();
+        let consequence = &if_exp.consequence;
+        self.compile_block_value(&consequence.statements, &consequence.span)?;
+        let to_end = self.emit(Opcode::Jump, &[PLACEHOLDER], span);
+
+        let alternative = self.jump_target(span)?;
+        self.patch_jump(to_alternative, alternative);
+        match &if_exp.alternative {
+            Some(block) => self.compile_block_value(&block.statements, &block.span)?,
+            None => {
+                self.emit(Opcode::Null, &[], span);
+            }
+        }
+        self.symbols.leave_branch();
+        // Only one of the branches runs
+        self.scope_mut().depth -= 1;
+
+        let end = self.jump_target(span)?;
+        self.patch_jump(to_end, end);
+        Ok(())
+    }
+
+    /// Compiles the body in its own scope and emits the closure
+    fn compile_function(&mut self, function: &FunctionLiteral) -> Result<(), CompileError> {
+        let span = &function.span;
+
+        self.scopes.push(CompilationScope::default());
+        self.symbols = SymbolTable::new_enclosed(std::mem::take(&mut self.symbols));
+
+        for param in function.parameters.iter() {
+            let symbol = self.symbols.define_parameter(&param.name);
+            check_count(symbol.index + 1, "locals", 1 << 8, &param.span)?;
+        }
+        let body = &function.body;
+        self.declare(&body.statements)?;
+        self.compile_block_value(&body.statements, &body.span)?;
+        self.emit(Opcode::ReturnValue, &[], &body.span);
+
+        let scope = self.scopes.pop().expect("The scope was pushed above");
+        let symbols = std::mem::take(&mut self.symbols);
+        let num_locals = symbols.num_locals;
+        let captures: Vec<Capture> = symbols
+            .free_symbols
+            .iter()
+            .map(|symbol| match symbol.scope {
+                SymbolScope::Local => Capture::Local(symbol.index),
+                SymbolScope::Free => Capture::Free(symbol.index),
+                SymbolScope::Global => unreachable!("Globals are not captured"),
+            })
+            .collect();
+        self.symbols = symbols.into_outer().expect("The table was enclosed above");
+        check_count(captures.len(), "captured variables", 1 << 8, span)?;
+
+        let compiled = CompiledFunction {
+            instructions: scope.instructions,
+            spans: scope.spans,
+            num_locals,
+            num_parameters: function.parameters.len(),
+            captures,
+        };
+        let index = self.add_constant(Object::CompiledFunction(Rc::new(compiled)), span)?;
+        self.emit(Opcode::Closure, &[index], span);
+        Ok(())
+    }
+
+    /// The value of an assignment is the assigned value, collections are values so assigning an
+    /// element stores an updated copy of the collection in its own target
+    fn compile_assign(&mut self, assign: &AssignExp) -> Result<(), CompileError> {
+        let span = &assign.span;
+
+        let (root, indexes) = assignment_place(&assign.target, span)?;
+        let symbols = self.symbols.resolve(&root.name);
+        check_count(indexes.len(), "nested indexes", u8::MAX as usize, span)?;
+        let compound = assign.operator.kind.compound_operator();
+
+        if indexes.is_empty() {
+            // Like the evaluator, the variable must exist before the value is evaluated, it
+            // always does when the name refers to a variable that is always set
+            let checked = symbols
+                .last()
+                .is_some_and(|s| s.scope == SymbolScope::Global);
+            match compound {
+                Some(operator) => {
+                    self.emit_load(&symbols, Opcode::CheckGlobal, span)?;
+                    self.compile_expression(&assign.value)?;
+                    let op = binary_opcode(&operator, span)?;
+                    self.emit(op, &[], span);
+                }
+                None => {
+                    if checked {
+                        self.emit_load(&symbols, Opcode::CheckGlobal, span)?;
+                        self.emit(Opcode::Pop, &[], span);
+                    }
+                    self.compile_expression(&assign.value)?;
+                }
+            }
+            // The value is kept as the value of the assignment
+            self.emit(Opcode::Dup, &[], span);
+        } else {
+            // The indexes are evaluated once, before the value, and stay on the stack for both
+            // the read and the store
+            for index in indexes.iter() {
+                self.compile_expression(index)?;
+            }
+            match compound {
+                Some(operator) => {
+                    self.emit_load(&symbols, Opcode::GetGlobal, span)?;
+                    self.emit(Opcode::IndexPath, &[indexes.len()], span);
+                    self.compile_expression(&assign.value)?;
+                    let op = binary_opcode(&operator, span)?;
+                    self.emit(op, &[], span);
+                }
+                None => self.compile_expression(&assign.value)?,
+            }
+            self.emit_load(&symbols, Opcode::GetGlobal, span)?;
+            self.emit(Opcode::SetPath, &[indexes.len()], span);
+        }
+
+        self.emit_store(&symbols, span)
+    }
+
+    /// Defines the lets of the body of a scope before it is compiled
+    fn declare(&mut self, statements: &[Statement]) -> Result<(), CompileError> {
+        let mut lets = vec![];
+        declared_names(statements, &mut lets);
+        for (name, span) in lets {
+            self.define(name, span)?;
+        }
+        Ok(())
+    }
+
+    fn define(&mut self, name: &str, span: &Span) -> Result<Symbol, CompileError> {
+        let symbol = self.symbols.define(name);
+        match symbol.scope {
+            SymbolScope::Global => check_count(symbol.index + 1, "globals", 1 << 16, span)?,
+            _ => check_count(symbol.index + 1, "locals", 1 << 8, span)?,
+        }
+        Ok(symbol)
+    }
+
+    /// Pushes the value of the first of the variables that is set, the last one is read with the
+    /// given instruction when it is a global
+    fn emit_load(
+        &mut self,
+        symbols: &[Symbol],
+        get_global: Opcode,
+        span: &Span,
+    ) -> Result<(), CompileError> {
+        let (last, others) = symbols
+            .split_last()
+            .expect("A name has at least a variable");
+
+        let mut jumps = vec![];
+        for symbol in others {
+            let op = match symbol.scope {
+                SymbolScope::Local => Opcode::TryGetLocal,
+                SymbolScope::Free => Opcode::TryGetFree,
+                SymbolScope::Global => unreachable!("The global of a name is its last variable"),
+            };
+            jumps.push(self.emit(op, &[symbol.index, PLACEHOLDER], span));
+        }
+        let op = match last.scope {
+            SymbolScope::Global => get_global,
+            SymbolScope::Local => Opcode::GetLocal,
+            SymbolScope::Free => Opcode::GetFree,
+        };
+        self.emit(op, &[last.index], span);
+
+        let end = self.jump_target(span)?;
+        for jump in jumps {
+            self.patch_jump(jump, end);
+        }
+        Ok(())
+    }
+
+    /// Pops the value of an assignment into the first of the variables that is set
+    fn emit_store(&mut self, symbols: &[Symbol], span: &Span) -> Result<(), CompileError> {
+        let (last, others) = symbols
+            .split_last()
+            .expect("A name has at least a variable");
+
+        let mut jumps = vec![];
+        for symbol in others {
+            let op = match symbol.scope {
+                SymbolScope::Local => Opcode::TrySetLocal,
+                SymbolScope::Free => Opcode::TrySetFree,
+                SymbolScope::Global => unreachable!("The global of a name is its last variable"),
+            };
+            jumps.push(self.emit(op, &[symbol.index, PLACEHOLDER], span));
+        }
+        let op = match last.scope {
+            SymbolScope::Global => Opcode::AssignGlobal,
+            SymbolScope::Local => Opcode::SetLocal,
+            SymbolScope::Free => Opcode::SetFree,
+        };
+        self.emit(op, &[last.index], span);
+
+        let end = self.jump_target(span)?;
+        for jump in jumps {
+            self.patch_jump(jump, end);
+        }
+        Ok(())
+    }
+
+    fn add_constant(&mut self, obj: Object, span: &Span) -> Result<usize, CompileError> {
+        check_count(self.constants.len() + 1, "constants", 1 << 16, span)?;
+        self.constants.push(obj);
+        Ok(self.constants.len() - 1)
+    }
+
+    fn emit_constant(&mut self, obj: Object, span: &Span) -> Result<(), CompileError> {
+        let index = self.add_constant(obj, span)?;
+        self.emit(Opcode::Constant, &[index], span);
+        Ok(())
+    }
+
+    /// Appends an instruction to the current scope, returns its offset
+    fn emit(&mut self, op: Opcode, operands: &[usize], span: &Span) -> usize {
+        let scope = self.scope_mut();
+        let offset = scope.instructions.len();
+
+        // Consecutive instructions of the same node share an entry
+        if scope.spans.last().is_none_or(|(_, last)| last != span) {
+            scope.spans.push((offset, span.clone()));
+        }
+        scope.instructions.extend(make(op, operands));
+        let (taken, left) = stack_use(op, operands);
+        scope.depth += left as isize - taken as isize;
+
+        offset
+    }
+
+    /// Offset of the next instruction, as the operand of a jump
+    fn jump_target(&self, span: &Span) -> Result<usize, CompileError> {
+        let offset = self.scope().instructions.len();
+        check_count(offset, "bytes of instructions", u16::MAX as usize, span)?;
+        Ok(offset)
+    }
+
+    /// Replaces the placeholder operand of the jump at the offset, the target is its last operand
+    fn patch_jump(&mut self, offset: usize, target: usize) {
+        let instructions = &mut self.scope_mut().instructions;
+        let op = Opcode::from_byte(instructions[offset]).expect("Only valid opcodes are emitted");
+        let widths = op.definition().operand_widths;
+        let operand = offset + 1 + widths[..widths.len() - 1].iter().sum::<usize>();
+        instructions[operand..operand + 2].copy_from_slice(&(target as u16).to_be_bytes());
+    }
+
+    fn scope(&self) -> &CompilationScope {
+        self.scopes.last().expect("The main scope is never left")
+    }
+
+    fn scope_mut(&mut self) -> &mut CompilationScope {
+        self.scopes
+            .last_mut()
+            .expect("The main scope is never left")
+    }
+}
+
+/// Errors if there are more than max of something, the limits come from the operand widths
+fn check_count(
+    count: usize,
+    what: &'static str,
+    max: usize,
+    span: &Span,
+) -> Result<(), CompileError> {
+    if count > max {
+        return Err(CompileError::TooMany {
+            what,
+            max,
+            span: span.clone(),
+        });
+    }
+    Ok(())
+}
+
+fn binary_opcode(operator: &TokenKind, span: &Span) -> Result<Opcode, CompileError> {
+    let op = match operator {
+        TokenKind::Plus => Opcode::Add,
+        TokenKind::Minus => Opcode::Sub,
+        TokenKind::Star => Opcode::Mul,
+        TokenKind::Slash => Opcode::Div,
+        TokenKind::Percent => Opcode::Mod,
+        TokenKind::BitAnd => Opcode::BitAnd,
+        TokenKind::BitOr => Opcode::BitOr,
+        TokenKind::BitXor => Opcode::BitXor,
+        TokenKind::ShiftLeft => Opcode::ShiftLeft,
+        TokenKind::ShiftRight => Opcode::ShiftRight,
+        TokenKind::Eq => Opcode::Equal,
+        TokenKind::NotEq => Opcode::NotEqual,
+        TokenKind::LT => Opcode::LessThan,
+        TokenKind::LTEq => Opcode::LessEqual,
+        TokenKind::GT => Opcode::GreaterThan,
+        TokenKind::GTEq => Opcode::GreaterEqual,
+        _ => return Err(unknown_operator(operator, span)),
+    };
+    Ok(op)
+}
+
+fn unknown_operator(operator: &TokenKind, span: &Span) -> CompileError {
+    CompileError::UnknownOperator {
+        operator: operator.clone(),
+        span: span.clone(),
+    }
+}
+
+/// Splits the target of an assignment into its variable and its indexes, outermost first
+fn assignment_place<'a>(
+    target: &'a Expression,
+    span: &Span,
+) -> Result<(&'a Ident, Vec<&'a Expression>), CompileError> {
+    match target {
+        Expression::Identifier(ident) => Ok((ident, vec![])),
+        Expression::Index(index_exp) => {
+            let (root, mut indexes) = assignment_place(&index_exp.left, span)?;
+            indexes.push(&index_exp.index);
+            Ok((root, indexes))
+        }
+        // The parser only accepts variables and indexes
+        _ => Err(CompileError::SyntaxError { span: span.clone() }),
+    }
+}
+
+/// Collects the names the lets of a scope define with their spans, the functions and the for loops
+/// in it are scopes of their own but the iterables of the loops are not
+fn declared_names<'a>(statements: &'a [Statement], names: &mut Vec<(&'a str, &'a Span)>) {
+    for stmt in statements {
+        match stmt {
+            Statement::Let(let_stmt) => {
+                if let TokenKind::Ident { name } = &let_stmt.identifier.kind {
+                    names.push((name, &let_stmt.span));
+                }
+                expression_lets(&let_stmt.exp, names);
+            }
+            Statement::Expression(exp) => expression_lets(exp, names),
+            Statement::Return(ret) => {
+                if let Some(exp) = &ret.exp {
+                    expression_lets(exp, names);
+                }
+            }
+            Statement::While(while_stmt) => {
+                expression_lets(&while_stmt.cond, names);
+                declared_names(&while_stmt.body.statements, names);
+            }
+            Statement::For(for_stmt) => expression_lets(&for_stmt.iterable, names),
+            Statement::Break(_) | Statement::Continue(_) | Statement::Error(_) => {}
+        }
+    }
+}
+
+/// The lets of the blocks of the if expressions in an expression
+fn expression_lets<'a>(exp: &'a Expression, names: &mut Vec<(&'a str, &'a Span)>) {
+    match exp {
+        Expression::If(if_exp) => {
+            expression_lets(&if_exp.cond, names);
+            declared_names(&if_exp.consequence.statements, names);
+            if let Some(alternative) = &if_exp.alternative {
+                declared_names(&alternative.statements, names);
+            }
+        }
+        Expression::Prefix(prefix) => expression_lets(&prefix.right, names),
+        Expression::Infix(infix) => {
+            expression_lets(&infix.left, names);
+            expression_lets(&infix.right, names);
+        }
+        Expression::Call(call) => {
+            expression_lets(&call.function, names);
+            for arg in call.arguments.iter() {
+                expression_lets(arg, names);
+            }
+        }
+        Expression::Array(array) => {
+            for element in array.elements.iter() {
+                expression_lets(element, names);
+            }
+        }
+        Expression::Hash(hash) => {
+            for (key, value) in hash.pairs.iter() {
+                expression_lets(key, names);
+                expression_lets(value, names);
+            }
+        }
+        Expression::Index(index_exp) => {
+            expression_lets(&index_exp.left, names);
+            expression_lets(&index_exp.index, names);
+        }
+        Expression::Slice(slice) => {
+            expression_lets(&slice.left, names);
+            for bound in [&slice.start, &slice.end].into_iter().flatten() {
+                expression_lets(bound, names);
+            }
+        }
+        Expression::Range(range) => {
+            expression_lets(&range.start, names);
+            expression_lets(&range.end, names);
+        }
+        Expression::Assign(assign) => {
+            expression_lets(&assign.target, names);
+            expression_lets(&assign.value, names);
+        }
+        Expression::Literal(_) | Expression::Identifier(_) | Expression::Function(_) => {}
+    }
+}
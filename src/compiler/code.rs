@@ -0,0 +1,457 @@
+use std::fmt::Display;
+
+use crate::lexer::token::Span;
+
+/// Bytes of the compiled code, each instruction is an opcode followed by its operands in big
+/// endian
+pub type Instructions = Vec<u8>;
+
+/// Source span of the instructions starting at each offset, an entry applies until the next one
+pub type SpanTable = Vec<(usize, Span)>;
+
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Opcode {
+    /// Pushes the constant at the index of the pool
+    Constant,
+    Pop,
+    /// Pushes a copy of the value on top of the stack
+    Dup,
+    True,
+    False,
+    Null,
+
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+    BitAnd,
+    BitOr,
+    BitXor,
+    ShiftLeft,
+    ShiftRight,
+
+    Equal,
+    NotEqual,
+    LessThan,
+    LessEqual,
+    GreaterThan,
+    GreaterEqual,
+
+    Minus,
+    Bang,
+    BitNot,
+
+    /// Jumps to an absolute offset of the instructions
+    Jump,
+    /// Pops the condition and jumps if it is not truthy
+    JumpNotTruthy,
+
+    /// Pushes the global, or the builtin of its name while it is not set
+    GetGlobal,
+    /// Pops the value of a let statement into the global
+    SetGlobal,
+    /// Pops the value of an assignment into the global, fails if it is not set
+    AssignGlobal,
+    /// Pushes the global, fails if it is not set since builtins cannot be assigned
+    CheckGlobal,
+    /// Pushes a local that is always set when the instruction runs
+    GetLocal,
+    SetLocal,
+    /// Pushes the local and jumps to the offset if it is set, does nothing otherwise so the next
+    /// variable of the name is tried
+    TryGetLocal,
+    /// Pops the value into the local and jumps to the offset if it is set, does nothing otherwise
+    TrySetLocal,
+    /// Gives new empty variables to the locals from the first to the last slot, run on each
+    /// iteration of a for loop so the closures of different iterations do not share them
+    NewLocals,
+    /// Free variables work like the locals, they are the variables captured by the closure
+    GetFree,
+    SetFree,
+    TryGetFree,
+    TrySetFree,
+
+    /// Builds an array from the given number of elements on the stack
+    Array,
+    /// Builds a hash from the given number of keys and values on the stack
+    Hash,
+    Index,
+    /// Fails if the value on top of the stack cannot be sliced, it is left on the stack
+    CheckSliceable,
+    /// Fails if the value on top of the stack cannot be a bound of a slice, it is left on the
+    /// stack
+    CheckSliceBound,
+    /// Pops the bounds given by the `SLICE_START` and `SLICE_END` flags then the collection
+    Slice,
+    /// Pops the collection and indexes it with the given number of indexes below it, they are
+    /// left on the stack, used to read the target of a compound assignment
+    IndexPath,
+    /// Pops the collection, the value and the given number of indexes below them, pushes the
+    /// value back then a copy of the collection with the value stored at the end of the indexes
+    SetPath,
+    /// Fails if the value on top of the stack cannot be a bound of a range, it is left on the
+    /// stack
+    CheckRangeBound,
+    Range,
+
+    /// Calls the function below the given number of arguments
+    Call,
+    ReturnValue,
+    /// Returns null
+    Return,
+    /// Stops the vm with the string constant at the index as the error, for a break or a continue
+    /// that is not in a loop
+    Fail,
+    /// Builds a closure from the function constant at the index, it shares the variables listed
+    /// in the captures of the function, taken from the locals or the free variables of the
+    /// current call
+    Closure,
+
+    /// Replaces the iterable on top of the stack by what a for loop iterates over and pushes the
+    /// position of the next item
+    IterStart,
+    /// Pushes the next item of the iteration, jumps to the offset when there are no more items
+    IterNext,
+}
+
+/// Flag of `OpSlice` set when the slice has a start bound
+pub const SLICE_START: usize = 1;
+/// Flag of `OpSlice` set when the slice has an end bound
+pub const SLICE_END: usize = 2;
+
+/// Every opcode, in the order of their byte
+const OPCODES: [Opcode; 57] = [
+    Opcode::Constant,
+    Opcode::Pop,
+    Opcode::Dup,
+    Opcode::True,
+    Opcode::False,
+    Opcode::Null,
+    Opcode::Add,
+    Opcode::Sub,
+    Opcode::Mul,
+    Opcode::Div,
+    Opcode::Mod,
+    Opcode::BitAnd,
+    Opcode::BitOr,
+    Opcode::BitXor,
+    Opcode::ShiftLeft,
+    Opcode::ShiftRight,
+    Opcode::Equal,
+    Opcode::NotEqual,
+    Opcode::LessThan,
+    Opcode::LessEqual,
+    Opcode::GreaterThan,
+    Opcode::GreaterEqual,
+    Opcode::Minus,
+    Opcode::Bang,
+    Opcode::BitNot,
+    Opcode::Jump,
+    Opcode::JumpNotTruthy,
+    Opcode::GetGlobal,
+    Opcode::SetGlobal,
+    Opcode::AssignGlobal,
+    Opcode::CheckGlobal,
+    Opcode::GetLocal,
+    Opcode::SetLocal,
+    Opcode::TryGetLocal,
+    Opcode::TrySetLocal,
+    Opcode::NewLocals,
+    Opcode::GetFree,
+    Opcode::SetFree,
+    Opcode::TryGetFree,
+    Opcode::TrySetFree,
+    Opcode::Array,
+    Opcode::Hash,
+    Opcode::Index,
+    Opcode::CheckSliceable,
+    Opcode::CheckSliceBound,
+    Opcode::Slice,
+    Opcode::IndexPath,
+    Opcode::SetPath,
+    Opcode::CheckRangeBound,
+    Opcode::Range,
+    Opcode::Call,
+    Opcode::ReturnValue,
+    Opcode::Return,
+    Opcode::Fail,
+    Opcode::Closure,
+    Opcode::IterStart,
+    Opcode::IterNext,
+];
+
+/// Name and operand widths in bytes of an opcode
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Definition {
+    pub name: &'static str,
+    pub operand_widths: &'static [usize],
+}
+
+impl Opcode {
+    /// Decodes an opcode, returns None if the byte is not one
+    pub fn from_byte(byte: u8) -> Option<Opcode> {
+        OPCODES.get(byte as usize).copied()
+    }
+
+    pub fn definition(self) -> Definition {
+        let (name, operand_widths): (&'static str, &'static [usize]) = match self {
+            Opcode::Constant => ("OpConstant", &[2]),
+            Opcode::Pop => ("OpPop", &[]),
+            Opcode::Dup => ("OpDup", &[]),
+            Opcode::True => ("OpTrue", &[]),
+            Opcode::False => ("OpFalse", &[]),
+            Opcode::Null => ("OpNull", &[]),
+            Opcode::Add => ("OpAdd", &[]),
+            Opcode::Sub => ("OpSub", &[]),
+            Opcode::Mul => ("OpMul", &[]),
+            Opcode::Div => ("OpDiv", &[]),
+            Opcode::Mod => ("OpMod", &[]),
+            Opcode::BitAnd => ("OpBitAnd", &[]),
+            Opcode::BitOr => ("OpBitOr", &[]),
+            Opcode::BitXor => ("OpBitXor", &[]),
+            Opcode::ShiftLeft => ("OpShiftLeft", &[]),
+            Opcode::ShiftRight => ("OpShiftRight", &[]),
+            Opcode::Equal => ("OpEqual", &[]),
+            Opcode::NotEqual => ("OpNotEqual", &[]),
+            Opcode::LessThan => ("OpLessThan", &[]),
+            Opcode::LessEqual => ("OpLessEqual", &[]),
+            Opcode::GreaterThan => ("OpGreaterThan", &[]),
+            Opcode::GreaterEqual => ("OpGreaterEqual", &[]),
+            Opcode::Minus => ("OpMinus", &[]),
+            Opcode::Bang => ("OpBang", &[]),
+            Opcode::BitNot => ("OpBitNot", &[]),
+            Opcode::Jump => ("OpJump", &[2]),
+            Opcode::JumpNotTruthy => ("OpJumpNotTruthy", &[2]),
+            Opcode::GetGlobal => ("OpGetGlobal", &[2]),
+            Opcode::SetGlobal => ("OpSetGlobal", &[2]),
+            Opcode::AssignGlobal => ("OpAssignGlobal", &[2]),
+            Opcode::CheckGlobal => ("OpCheckGlobal", &[2]),
+            Opcode::GetLocal => ("OpGetLocal", &[1]),
+            Opcode::SetLocal => ("OpSetLocal", &[1]),
+            Opcode::TryGetLocal => ("OpTryGetLocal", &[1, 2]),
+            Opcode::TrySetLocal => ("OpTrySetLocal", &[1, 2]),
+            Opcode::NewLocals => ("OpNewLocals", &[1, 1]),
+            Opcode::GetFree => ("OpGetFree", &[1]),
+            Opcode::SetFree => ("OpSetFree", &[1]),
+            Opcode::TryGetFree => ("OpTryGetFree", &[1, 2]),
+            Opcode::TrySetFree => ("OpTrySetFree", &[1, 2]),
+            Opcode::Array => ("OpArray", &[2]),
+            Opcode::Hash => ("OpHash", &[2]),
+            Opcode::Index => ("OpIndex", &[]),
+            Opcode::CheckSliceable => ("OpCheckSliceable", &[]),
+            Opcode::CheckSliceBound => ("OpCheckSliceBound", &[]),
+            Opcode::Slice => ("OpSlice", &[1]),
+            Opcode::IndexPath => ("OpIndexPath", &[1]),
+            Opcode::SetPath => ("OpSetPath", &[1]),
+            Opcode::CheckRangeBound => ("OpCheckRangeBound", &[]),
+            Opcode::Range => ("OpRange", &[]),
+            Opcode::Call => ("OpCall", &[1]),
+            Opcode::ReturnValue => ("OpReturnValue", &[]),
+            Opcode::Return => ("OpReturn", &[]),
+            Opcode::Fail => ("OpFail", &[2]),
+            Opcode::Closure => ("OpClosure", &[2]),
+            Opcode::IterStart => ("OpIterStart", &[]),
+            Opcode::IterNext => ("OpIterNext", &[2]),
+        };
+
+        Definition {
+            name,
+            operand_widths,
+        }
+    }
+}
+
+impl Display for Opcode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.definition().name)
+    }
+}
+
+/// Number of values an instruction takes from the top of the stack and number it leaves in their
+/// place, when it does not jump, the values it only reads count as taken and left
+pub fn stack_use(op: Opcode, operands: &[usize]) -> (usize, usize) {
+    match op {
+        Opcode::Constant
+        | Opcode::True
+        | Opcode::False
+        | Opcode::Null
+        | Opcode::GetGlobal
+        | Opcode::CheckGlobal
+        | Opcode::GetLocal
+        | Opcode::GetFree
+        | Opcode::Closure => (0, 1),
+        Opcode::Jump
+        | Opcode::Return
+        | Opcode::Fail
+        | Opcode::NewLocals
+        // The try instructions only push or pop a value when they jump
+        | Opcode::TryGetLocal
+        | Opcode::TryGetFree => (0, 0),
+        Opcode::Pop
+        | Opcode::JumpNotTruthy
+        | Opcode::SetGlobal
+        | Opcode::AssignGlobal
+        | Opcode::SetLocal
+        | Opcode::SetFree
+        | Opcode::ReturnValue => (1, 0),
+        Opcode::Minus
+        | Opcode::Bang
+        | Opcode::BitNot
+        | Opcode::TrySetLocal
+        | Opcode::TrySetFree
+        | Opcode::CheckSliceable
+        | Opcode::CheckSliceBound
+        | Opcode::CheckRangeBound => (1, 1),
+        Opcode::Dup | Opcode::IterStart => (1, 2),
+        Opcode::Add
+        | Opcode::Sub
+        | Opcode::Mul
+        | Opcode::Div
+        | Opcode::Mod
+        | Opcode::BitAnd
+        | Opcode::BitOr
+        | Opcode::BitXor
+        | Opcode::ShiftLeft
+        | Opcode::ShiftRight
+        | Opcode::Equal
+        | Opcode::NotEqual
+        | Opcode::LessThan
+        | Opcode::LessEqual
+        | Opcode::GreaterThan
+        | Opcode::GreaterEqual
+        | Opcode::Index
+        | Opcode::Range => (2, 1),
+        // The iterable and the position, then the next item
+        Opcode::IterNext => (2, 3),
+        Opcode::Array => (operands[0], 1),
+        Opcode::Hash => (2 * operands[0], 1),
+        Opcode::Slice => (1 + operands[0].count_ones() as usize, 1),
+        // The indexes stay below the collection
+        Opcode::IndexPath => (operands[0] + 1, operands[0] + 1),
+        // The indexes, the value and the collection, then the value and the updated collection
+        Opcode::SetPath => (operands[0] + 2, 2),
+        // The function and its arguments, then the value it returns
+        Opcode::Call => (operands[0] + 1, 1),
+    }
+}
+
+/// Encodes an instruction, operands that do not fit in their width are truncated so the caller
+/// must check them first
+pub fn make(op: Opcode, operands: &[usize]) -> Vec<u8> {
+    let def = op.definition();
+    let len = 1 + def.operand_widths.iter().sum::<usize>();
+
+    let mut instruction = Vec::with_capacity(len);
+    instruction.push(op as u8);
+
+    for (operand, width) in operands.iter().zip(def.operand_widths) {
+        match width {
+            2 => instruction.extend_from_slice(&(*operand as u16).to_be_bytes()),
+            1 => instruction.push(*operand as u8),
+            _ => unreachable!("Operands are either one or two bytes wide"),
+        }
+    }
+
+    instruction
+}
+
+/// Decodes the operands of an instruction, the bytes start right after the opcode
+/// Returns the operands and the number of bytes read
+pub fn read_operands(def: &Definition, bytes: &[u8]) -> (Vec<usize>, usize) {
+    let mut operands = Vec::with_capacity(def.operand_widths.len());
+    let mut offset = 0;
+
+    for width in def.operand_widths {
+        match width {
+            2 => operands.push(read_u16(bytes, offset)),
+            1 => operands.push(bytes[offset] as usize),
+            _ => unreachable!("Operands are either one or two bytes wide"),
+        }
+        offset += width;
+    }
+
+    (operands, offset)
+}
+
+pub fn read_u16(bytes: &[u8], offset: usize) -> usize {
+    u16::from_be_bytes([bytes[offset], bytes[offset + 1]]) as usize
+}
+
+/// Returns the span of the instruction at the offset
+pub fn span_at(spans: &SpanTable, offset: usize) -> Option<&Span> {
+    let entries = spans.partition_point(|(start, _)| *start <= offset);
+    spans[..entries].last().map(|(_, span)| span)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn opcode_bytes() {
+        for (byte, op) in OPCODES.iter().enumerate() {
+            assert_eq!(*op as u8 as usize, byte, "{op} is not at its byte");
+            assert_eq!(Opcode::from_byte(byte as u8), Some(*op));
+        }
+        assert_eq!(Opcode::from_byte(OPCODES.len() as u8), None);
+    }
+
+    #[test]
+    fn make_instructions() {
+        let cases = [
+            (
+                Opcode::Constant,
+                vec![65534],
+                vec![Opcode::Constant as u8, 255, 254],
+            ),
+            (Opcode::Add, vec![], vec![Opcode::Add as u8]),
+            (
+                Opcode::GetLocal,
+                vec![255],
+                vec![Opcode::GetLocal as u8, 255],
+            ),
+            (
+                Opcode::TryGetLocal,
+                vec![255, 65534],
+                vec![Opcode::TryGetLocal as u8, 255, 255, 254],
+            ),
+        ];
+
+        for (op, operands, expected) in cases {
+            assert_eq!(make(op, &operands), expected, "Unexpected bytes for {op}");
+        }
+    }
+
+    #[test]
+    fn spans_of_instructions() {
+        let spans = vec![
+            (0, Span { start: 0, end: 1 }),
+            (3, Span { start: 4, end: 5 }),
+        ];
+
+        assert_eq!(span_at(&spans, 0), Some(&Span { start: 0, end: 1 }));
+        assert_eq!(span_at(&spans, 2), Some(&Span { start: 0, end: 1 }));
+        assert_eq!(span_at(&spans, 3), Some(&Span { start: 4, end: 5 }));
+        assert_eq!(span_at(&spans, 10), Some(&Span { start: 4, end: 5 }));
+        assert_eq!(span_at(&vec![], 0), None);
+    }
+
+    #[test]
+    fn read_back_operands() {
+        let cases = [
+            (Opcode::Constant, vec![65535], 2),
+            (Opcode::GetLocal, vec![255], 1),
+            (Opcode::TryGetLocal, vec![255, 65535], 3),
+        ];
+
+        for (op, operands, bytes_read) in cases {
+            let instruction = make(op, &operands);
+            let (read, n) = read_operands(&op.definition(), &instruction[1..]);
+
+            assert_eq!(read, operands);
+            assert_eq!(n, bytes_read);
+        }
+    }
+}
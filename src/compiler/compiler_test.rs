@@ -0,0 +1,677 @@
+#[cfg(test)]
+mod test {
+    use crate::{
+        compiler::{
+            Bytecode, Compiler,
+            code::{Instructions, Opcode, SLICE_END, make},
+            error::CompileError,
+        },
+        lexer::{Lexer, token::Span},
+        object::{Capture, CompiledFunction, Object},
+        parser::Parser,
+    };
+
+    // Panics if the program cannot be parsed
+    fn compile(input: &str) -> Result<Bytecode, CompileError> {
+        let mut parser = Parser::new(Lexer::new(input));
+
+        match parser.parse_program() {
+            Ok(program) => Compiler::new().compile(&program),
+            Err(errs) => {
+                for err in errs.iter() {
+                    eprintln!("Parsing error: {}", err)
+                }
+                panic!("Parsing exited with errors")
+            }
+        }
+    }
+
+    fn test_cases(cases: &[(&str, Vec<Object>, Vec<Instructions>)]) {
+        for (input, constants, instructions) in cases.iter() {
+            let bytecode = compile(input).unwrap_or_else(|err| panic!("{input}: {err}"));
+
+            assert_eq!(
+                bytecode.main.instructions,
+                instructions.concat(),
+                "Unexpected instructions for: {input}"
+            );
+            assert_eq!(
+                &bytecode.constants, constants,
+                "Unexpected constants for: {input}"
+            );
+        }
+    }
+
+    /// Returns the compiled function at the index of the constant pool
+    fn function(bytecode: &Bytecode, index: usize) -> &CompiledFunction {
+        match &bytecode.constants[index] {
+            Object::CompiledFunction(function) => function,
+            obj => panic!("Expected a compiled function, got {obj}"),
+        }
+    }
+
+    fn int(value: i64) -> Object {
+        Object::Integer(value)
+    }
+
+    #[test]
+    fn integer_arithmetic() {
+        let cases = [
+            (
+                "1 + 2",
+                vec![int(1), int(2)],
+                vec![
+                    make(Opcode::Constant, &[0]),
+                    make(Opcode::Constant, &[1]),
+                    make(Opcode::Add, &[]),
+                    make(Opcode::ReturnValue, &[]),
+                ],
+            ),
+            (
+                "1; 2",
+                vec![int(1), int(2)],
+                vec![
+                    make(Opcode::Constant, &[0]),
+                    make(Opcode::Pop, &[]),
+                    make(Opcode::Constant, &[1]),
+                    make(Opcode::ReturnValue, &[]),
+                ],
+            ),
+            (
+                "-1 % ~2",
+                vec![int(1), int(2)],
+                vec![
+                    make(Opcode::Constant, &[0]),
+                    make(Opcode::Minus, &[]),
+                    make(Opcode::Constant, &[1]),
+                    make(Opcode::BitNot, &[]),
+                    make(Opcode::Mod, &[]),
+                    make(Opcode::ReturnValue, &[]),
+                ],
+            ),
+            (
+                "1.5 << 2",
+                vec![Object::Float(1.5), int(2)],
+                vec![
+                    make(Opcode::Constant, &[0]),
+                    make(Opcode::Constant, &[1]),
+                    make(Opcode::ShiftLeft, &[]),
+                    make(Opcode::ReturnValue, &[]),
+                ],
+            ),
+            (
+                "",
+                vec![],
+                vec![make(Opcode::Null, &[]), make(Opcode::ReturnValue, &[])],
+            ),
+        ];
+
+        test_cases(&cases);
+    }
+
+    #[test]
+    fn boolean_expressions() {
+        let cases = [
+            (
+                "true == !false",
+                vec![],
+                vec![
+                    make(Opcode::True, &[]),
+                    make(Opcode::False, &[]),
+                    make(Opcode::Bang, &[]),
+                    make(Opcode::Equal, &[]),
+                    make(Opcode::ReturnValue, &[]),
+                ],
+            ),
+            (
+                "1 > 2",
+                vec![int(1), int(2)],
+                vec![
+                    make(Opcode::Constant, &[0]),
+                    make(Opcode::Constant, &[1]),
+                    make(Opcode::GreaterThan, &[]),
+                    make(Opcode::ReturnValue, &[]),
+                ],
+            ),
+            (
+                "true && false",
+                vec![],
+                vec![
+                    // 0000
+                    make(Opcode::True, &[]),
+                    // 0001
+                    make(Opcode::JumpNotTruthy, &[12]),
+                    // 0004
+                    make(Opcode::False, &[]),
+                    // 0005
+                    make(Opcode::JumpNotTruthy, &[12]),
+                    // 0008
+                    make(Opcode::True, &[]),
+                    // 0009
+                    make(Opcode::Jump, &[13]),
+                    // 0012
+                    make(Opcode::False, &[]),
+                    // 0013
+                    make(Opcode::ReturnValue, &[]),
+                ],
+            ),
+            (
+                "true || false",
+                vec![],
+                vec![
+                    // 0000
+                    make(Opcode::True, &[]),
+                    // 0001
+                    make(Opcode::JumpNotTruthy, &[8]),
+                    // 0004
+                    make(Opcode::True, &[]),
+                    // 0005
+                    make(Opcode::Jump, &[17]),
+                    // 0008
+                    make(Opcode::False, &[]),
+                    // 0009
+                    make(Opcode::JumpNotTruthy, &[16]),
+                    // 0012
+                    make(Opcode::True, &[]),
+                    // 0013
+                    make(Opcode::Jump, &[17]),
+                    // 0016
+                    make(Opcode::False, &[]),
+                    // 0017
+                    make(Opcode::ReturnValue, &[]),
+                ],
+            ),
+        ];
+
+        test_cases(&cases);
+    }
+
+    #[test]
+    fn conditionals() {
+        let cases = [
+            (
+                "if (true) { 10 }; 3333;",
+                vec![int(10), int(3333)],
+                vec![
+                    // 0000
+                    make(Opcode::True, &[]),
+                    // 0001
+                    make(Opcode::JumpNotTruthy, &[10]),
+                    // 0004
+                    make(Opcode::Constant, &[0]),
+                    // 0007
+                    make(Opcode::Jump, &[11]),
+                    // 0010
+                    make(Opcode::Null, &[]),
+                    // 0011
+                    make(Opcode::Pop, &[]),
+                    // 0012
+                    make(Opcode::Constant, &[1]),
+                    // 0015
+                    make(Opcode::ReturnValue, &[]),
+                ],
+            ),
+            (
+                "if (true) { 10 } else { let a = 20; }",
+                vec![int(10), int(20)],
+                vec![
+                    // 0000
+                    make(Opcode::True, &[]),
+                    // 0001
+                    make(Opcode::JumpNotTruthy, &[10]),
+                    // 0004
+                    make(Opcode::Constant, &[0]),
+                    // 0007
+                    make(Opcode::Jump, &[17]),
+                    // 0010
+                    make(Opcode::Constant, &[1]),
+                    // 0013
+                    make(Opcode::SetGlobal, &[0]),
+                    // 0016
+                    make(Opcode::Null, &[]),
+                    // 0017
+                    make(Opcode::ReturnValue, &[]),
+                ],
+            ),
+        ];
+
+        test_cases(&cases);
+    }
+
+    #[test]
+    fn global_let_statements() {
+        let cases = [
+            (
+                "let one = 1; let two = one; two",
+                vec![int(1)],
+                vec![
+                    make(Opcode::Constant, &[0]),
+                    make(Opcode::SetGlobal, &[0]),
+                    make(Opcode::GetGlobal, &[0]),
+                    make(Opcode::SetGlobal, &[1]),
+                    make(Opcode::GetGlobal, &[1]),
+                    make(Opcode::ReturnValue, &[]),
+                ],
+            ),
+            (
+                "let one = 1; let one = 2;",
+                vec![int(1), int(2)],
+                vec![
+                    make(Opcode::Constant, &[0]),
+                    make(Opcode::SetGlobal, &[0]),
+                    make(Opcode::Constant, &[1]),
+                    make(Opcode::SetGlobal, &[0]),
+                    make(Opcode::Null, &[]),
+                    make(Opcode::ReturnValue, &[]),
+                ],
+            ),
+        ];
+
+        test_cases(&cases);
+    }
+
+    #[test]
+    fn collections() {
+        let cases = [
+            (
+                r#""mon" + "key""#,
+                vec![
+                    Object::String("mon".to_string()),
+                    Object::String("key".to_string()),
+                ],
+                vec![
+                    make(Opcode::Constant, &[0]),
+                    make(Opcode::Constant, &[1]),
+                    make(Opcode::Add, &[]),
+                    make(Opcode::ReturnValue, &[]),
+                ],
+            ),
+            (
+                "[1, 2][0]",
+                vec![int(1), int(2), int(0)],
+                vec![
+                    make(Opcode::Constant, &[0]),
+                    make(Opcode::Constant, &[1]),
+                    make(Opcode::Array, &[2]),
+                    make(Opcode::Constant, &[2]),
+                    make(Opcode::Index, &[]),
+                    make(Opcode::ReturnValue, &[]),
+                ],
+            ),
+            (
+                "{1: 2}",
+                vec![int(1), int(2)],
+                vec![
+                    make(Opcode::Constant, &[0]),
+                    make(Opcode::Constant, &[1]),
+                    make(Opcode::Hash, &[1]),
+                    make(Opcode::ReturnValue, &[]),
+                ],
+            ),
+            (
+                "[][:1]",
+                vec![int(1)],
+                vec![
+                    make(Opcode::Array, &[0]),
+                    make(Opcode::CheckSliceable, &[]),
+                    make(Opcode::Constant, &[0]),
+                    make(Opcode::CheckSliceBound, &[]),
+                    // Only the end bound
+                    make(Opcode::Slice, &[SLICE_END]),
+                    make(Opcode::ReturnValue, &[]),
+                ],
+            ),
+            (
+                "0..3",
+                vec![int(0), int(3)],
+                vec![
+                    make(Opcode::Constant, &[0]),
+                    make(Opcode::CheckRangeBound, &[]),
+                    make(Opcode::Constant, &[1]),
+                    make(Opcode::Range, &[]),
+                    make(Opcode::ReturnValue, &[]),
+                ],
+            ),
+        ];
+
+        test_cases(&cases);
+    }
+
+    #[test]
+    fn assignments() {
+        let cases = [
+            (
+                "let a = 1; a += 2",
+                vec![int(1), int(2)],
+                vec![
+                    make(Opcode::Constant, &[0]),
+                    make(Opcode::SetGlobal, &[0]),
+                    make(Opcode::CheckGlobal, &[0]),
+                    make(Opcode::Constant, &[1]),
+                    make(Opcode::Add, &[]),
+                    make(Opcode::Dup, &[]),
+                    make(Opcode::AssignGlobal, &[0]),
+                    make(Opcode::ReturnValue, &[]),
+                ],
+            ),
+            (
+                "let a = 1; a = 2",
+                vec![int(1), int(2)],
+                vec![
+                    make(Opcode::Constant, &[0]),
+                    make(Opcode::SetGlobal, &[0]),
+                    // The variable must exist before the value is evaluated
+                    make(Opcode::CheckGlobal, &[0]),
+                    make(Opcode::Pop, &[]),
+                    make(Opcode::Constant, &[1]),
+                    make(Opcode::Dup, &[]),
+                    make(Opcode::AssignGlobal, &[0]),
+                    make(Opcode::ReturnValue, &[]),
+                ],
+            ),
+            (
+                "let a = [[1]]; a[0][0] = 2",
+                vec![int(1), int(0), int(0), int(2)],
+                vec![
+                    make(Opcode::Constant, &[0]),
+                    make(Opcode::Array, &[1]),
+                    make(Opcode::Array, &[1]),
+                    make(Opcode::SetGlobal, &[0]),
+                    // The indexes, then the value
+                    make(Opcode::Constant, &[1]),
+                    make(Opcode::Constant, &[2]),
+                    make(Opcode::Constant, &[3]),
+                    // Leaves the value and a with the value stored at [0][0]
+                    make(Opcode::GetGlobal, &[0]),
+                    make(Opcode::SetPath, &[2]),
+                    make(Opcode::AssignGlobal, &[0]),
+                    make(Opcode::ReturnValue, &[]),
+                ],
+            ),
+            (
+                "let a = [1]; a[0] += 2",
+                vec![int(1), int(0), int(2)],
+                vec![
+                    make(Opcode::Constant, &[0]),
+                    make(Opcode::Array, &[1]),
+                    make(Opcode::SetGlobal, &[0]),
+                    // The index is evaluated once for the read and the store
+                    make(Opcode::Constant, &[1]),
+                    make(Opcode::GetGlobal, &[0]),
+                    make(Opcode::IndexPath, &[1]),
+                    make(Opcode::Constant, &[2]),
+                    make(Opcode::Add, &[]),
+                    make(Opcode::GetGlobal, &[0]),
+                    make(Opcode::SetPath, &[1]),
+                    make(Opcode::AssignGlobal, &[0]),
+                    make(Opcode::ReturnValue, &[]),
+                ],
+            ),
+        ];
+
+        test_cases(&cases);
+    }
+
+    #[test]
+    fn loops() {
+        let cases = [
+            (
+                "while (false) { break; }",
+                vec![],
+                vec![
+                    // 0000
+                    make(Opcode::False, &[]),
+                    // 0001
+                    make(Opcode::JumpNotTruthy, &[10]),
+                    // 0004
+                    make(Opcode::Jump, &[10]),
+                    // 0007
+                    make(Opcode::Jump, &[0]),
+                    // 0010
+                    make(Opcode::Null, &[]),
+                    // 0011
+                    make(Opcode::ReturnValue, &[]),
+                ],
+            ),
+            (
+                "for (x in [1]) { continue; }",
+                vec![int(1)],
+                vec![
+                    // 0000
+                    make(Opcode::Constant, &[0]),
+                    // 0003
+                    make(Opcode::Array, &[1]),
+                    // 0006
+                    make(Opcode::IterStart, &[]),
+                    // 0007
+                    make(Opcode::IterNext, &[21]),
+                    // 0010, each iteration has its own variables
+                    make(Opcode::NewLocals, &[0, 0]),
+                    // 0013
+                    make(Opcode::SetLocal, &[0]),
+                    // 0015
+                    make(Opcode::Jump, &[7]),
+                    // 0018
+                    make(Opcode::Jump, &[7]),
+                    // 0021
+                    make(Opcode::Pop, &[]),
+                    // 0022
+                    make(Opcode::Pop, &[]),
+                    // 0023
+                    make(Opcode::Null, &[]),
+                    // 0024
+                    make(Opcode::ReturnValue, &[]),
+                ],
+            ),
+            (
+                // The 1 is on the stack when the loop is left
+                "while (true) { 1 + if (true) { break; } else { 2 } }",
+                vec![int(1), int(2)],
+                vec![
+                    // 0000
+                    make(Opcode::True, &[]),
+                    // 0001
+                    make(Opcode::JumpNotTruthy, &[27]),
+                    // 0004
+                    make(Opcode::Constant, &[0]),
+                    // 0007
+                    make(Opcode::True, &[]),
+                    // 0008
+                    make(Opcode::JumpNotTruthy, &[19]),
+                    // 0011
+                    make(Opcode::Pop, &[]),
+                    // 0012
+                    make(Opcode::Jump, &[27]),
+                    // 0015
+                    make(Opcode::Null, &[]),
+                    // 0016
+                    make(Opcode::Jump, &[22]),
+                    // 0019
+                    make(Opcode::Constant, &[1]),
+                    // 0022
+                    make(Opcode::Add, &[]),
+                    // 0023
+                    make(Opcode::Pop, &[]),
+                    // 0024
+                    make(Opcode::Jump, &[0]),
+                    // 0027
+                    make(Opcode::Null, &[]),
+                    // 0028
+                    make(Opcode::ReturnValue, &[]),
+                ],
+            ),
+        ];
+
+        test_cases(&cases);
+
+        // Like in the evaluator, it is only an error when it runs
+        let bytecode = compile("if (false) { break; } 5").unwrap();
+        assert_eq!(
+            bytecode.constants[0],
+            Object::String("break outside of a loop".to_string())
+        );
+        assert_eq!(bytecode.main.instructions[4..7], make(Opcode::Fail, &[0]));
+    }
+
+    #[test]
+    fn functions() {
+        let bytecode = compile("fn() { return 5 + 10 }").unwrap();
+        assert_eq!(
+            bytecode.main.instructions,
+            [make(Opcode::Closure, &[2]), make(Opcode::ReturnValue, &[])].concat()
+        );
+        assert_eq!(
+            function(&bytecode, 2).instructions,
+            [
+                make(Opcode::Constant, &[0]),
+                make(Opcode::Constant, &[1]),
+                make(Opcode::Add, &[]),
+                make(Opcode::ReturnValue, &[]),
+                // The value of the body, never reached
+                make(Opcode::Null, &[]),
+                make(Opcode::ReturnValue, &[]),
+            ]
+            .concat()
+        );
+
+        let bytecode = compile("fn() { }").unwrap();
+        assert_eq!(
+            function(&bytecode, 0).instructions,
+            [make(Opcode::Null, &[]), make(Opcode::ReturnValue, &[])].concat()
+        );
+
+        let bytecode = compile("fn(a) { let b = a; b }").unwrap();
+        let compiled = function(&bytecode, 0);
+        assert_eq!(
+            compiled.instructions,
+            [
+                make(Opcode::GetLocal, &[0]),
+                make(Opcode::SetLocal, &[1]),
+                make(Opcode::GetLocal, &[1]),
+                make(Opcode::ReturnValue, &[]),
+            ]
+            .concat()
+        );
+        assert_eq!(compiled.num_locals, 2);
+        assert_eq!(compiled.num_parameters, 1);
+    }
+
+    #[test]
+    fn builtins_and_calls() {
+        let bytecode = compile("len([]); push([], 1)").unwrap();
+        assert_eq!(
+            bytecode.main.instructions,
+            [
+                // The builtins are the globals that are never set
+                make(Opcode::GetGlobal, &[0]),
+                make(Opcode::Array, &[0]),
+                make(Opcode::Call, &[1]),
+                make(Opcode::Pop, &[]),
+                make(Opcode::GetGlobal, &[1]),
+                make(Opcode::Array, &[0]),
+                make(Opcode::Constant, &[0]),
+                make(Opcode::Call, &[2]),
+                make(Opcode::ReturnValue, &[]),
+            ]
+            .concat()
+        );
+    }
+
+    #[test]
+    fn closures() {
+        let bytecode = compile("fn(a) { fn(b) { a + b } }").unwrap();
+        assert_eq!(
+            function(&bytecode, 0).instructions,
+            [
+                make(Opcode::GetFree, &[0]),
+                make(Opcode::GetLocal, &[0]),
+                make(Opcode::Add, &[]),
+                make(Opcode::ReturnValue, &[]),
+            ]
+            .concat()
+        );
+        assert_eq!(function(&bytecode, 0).captures, [Capture::Local(0)]);
+        assert_eq!(
+            function(&bytecode, 1).instructions,
+            [make(Opcode::Closure, &[0]), make(Opcode::ReturnValue, &[]),].concat()
+        );
+        assert_eq!(
+            bytecode.main.instructions,
+            [make(Opcode::Closure, &[1]), make(Opcode::ReturnValue, &[])].concat()
+        );
+
+        // x is the local defined after the closure once its let has run, the global otherwise
+        let bytecode = compile("fn() { let f = fn() { x }; let x = 1; f }").unwrap();
+        assert_eq!(
+            function(&bytecode, 0).instructions,
+            [
+                make(Opcode::TryGetFree, &[0, 7]),
+                make(Opcode::GetGlobal, &[0]),
+                make(Opcode::ReturnValue, &[]),
+            ]
+            .concat()
+        );
+        assert_eq!(function(&bytecode, 0).captures, [Capture::Local(1)]);
+        assert_eq!(bytecode.globals, ["x"]);
+    }
+
+    #[test]
+    fn recursive_functions() {
+        let bytecode = compile("let countDown = fn(x) { countDown(x - 1) }; countDown(1)").unwrap();
+        assert_eq!(
+            function(&bytecode, 1).instructions,
+            [
+                make(Opcode::GetGlobal, &[0]),
+                make(Opcode::GetLocal, &[0]),
+                make(Opcode::Constant, &[0]),
+                make(Opcode::Sub, &[]),
+                make(Opcode::Call, &[1]),
+                make(Opcode::ReturnValue, &[]),
+            ]
+            .concat()
+        );
+        assert_eq!(
+            bytecode.main.instructions,
+            [
+                make(Opcode::Closure, &[1]),
+                make(Opcode::SetGlobal, &[0]),
+                make(Opcode::GetGlobal, &[0]),
+                make(Opcode::Constant, &[2]),
+                make(Opcode::Call, &[1]),
+                make(Opcode::ReturnValue, &[]),
+            ]
+            .concat()
+        );
+    }
+
+    #[test]
+    fn instruction_spans() {
+        let bytecode = compile("1 +\n2").unwrap();
+        assert_eq!(
+            bytecode.main.spans,
+            vec![
+                (0, Span { start: 0, end: 1 }),
+                (3, Span { start: 4, end: 5 }),
+                // The add and the return of the program
+                (6, Span { start: 0, end: 5 }),
+            ]
+        );
+    }
+
+    #[test]
+    fn compile_errors() {
+        let args = vec!["1"; 256].join(", ");
+        assert_eq!(
+            compile(&format!("len({args})")).unwrap_err().to_string(),
+            "too many arguments, the limit is 255"
+        );
+
+        let params: Vec<String> = (0..257).map(|i| format!("p{i}")).collect();
+        assert_eq!(
+            compile(&format!("fn({}) {{ }}", params.join(", ")))
+                .unwrap_err()
+                .to_string(),
+            "too many locals, the limit is 256"
+        );
+    }
+}
@@ -0,0 +1,125 @@
+use crate::{
+    compiler::{
+        Bytecode,
+        code::{Opcode, read_operands, span_at},
+    },
+    object::{CompiledFunction, Object},
+};
+
+/// Width of the instruction column, the spans are aligned after it
+const INSTRUCTION_WIDTH: usize = 28;
+
+/// Lists the instructions of the main function then the ones of each function of the constant
+/// pool
+pub fn disassemble(bytecode: &Bytecode) -> String {
+    let mut out = format!("== main: {} ==\n", bytecode.main);
+    out += &disassemble_function(&bytecode.main, &bytecode.constants);
+
+    for (index, constant) in bytecode.constants.iter().enumerate() {
+        if let Object::CompiledFunction(function) = constant {
+            out += &format!("\n== constant {index}: {function} ==\n");
+            out += &disassemble_function(function, &bytecode.constants);
+        }
+    }
+
+    out
+}
+
+/// Prints one instruction per line with its offset, its operands and the span of the code it was
+/// compiled from, the values of the constants are shown next to their index
+pub fn disassemble_function(function: &CompiledFunction, constants: &[Object]) -> String {
+    let instructions = &function.instructions;
+    let mut out = String::new();
+    let mut offset = 0;
+
+    while offset < instructions.len() {
+        let Some(op) = Opcode::from_byte(instructions[offset]) else {
+            out += &format!("{offset:04} <unknown opcode {}>\n", instructions[offset]);
+            offset += 1;
+            continue;
+        };
+        let def = op.definition();
+        let width: usize = def.operand_widths.iter().sum();
+        let Some(bytes) = instructions.get(offset + 1..offset + 1 + width) else {
+            out += &format!("{offset:04} <truncated {op}>\n");
+            break;
+        };
+
+        let (operands, read) = read_operands(&def, bytes);
+        let mut text = op.to_string();
+        for operand in operands.iter() {
+            text += &format!(" {operand}");
+        }
+        if op == Opcode::Constant
+            && let Some(constant) = constants.get(operands[0])
+        {
+            text += &format!(" ({})", describe(constant));
+        }
+
+        let span = span_at(&function.spans, offset)
+            .map(|span| format!("{}..{}", span.start, span.end))
+            .unwrap_or_default();
+        let line = format!("{offset:04} {text:<INSTRUCTION_WIDTH$} {span}");
+        out += line.trim_end();
+        out += "\n";
+
+        offset += 1 + read;
+    }
+
+    out
+}
+
+/// Strings are quoted so their spaces can be seen
+fn describe(constant: &Object) -> String {
+    match constant {
+        Object::String(value) => format!("{value:?}"),
+        other => other.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{compiler::Compiler, lexer::Lexer, parser::Parser};
+
+    fn compile(input: &str) -> Bytecode {
+        let program = Parser::new(Lexer::new(input)).parse_program().unwrap();
+        Compiler::new().compile(&program).unwrap()
+    }
+
+    #[test]
+    fn disassemble_program() {
+        let input = r#"let add = fn(a) { a + 1 }; add("x")"#;
+        let expected = "\
+== main: compiled function (0 parameters, 15 bytes) ==
+0000 OpClosure 1                  10..25
+0003 OpSetGlobal 0                0..26
+0006 OpGetGlobal 0                27..30
+0009 OpConstant 2 (\"x\")           31..34
+0012 OpCall 1                     27..35
+0014 OpReturnValue                0..35
+
+== constant 1: compiled function (1 parameters, 7 bytes) ==
+0000 OpGetLocal 0                 18..19
+0002 OpConstant 0 (1)             22..23
+0005 OpAdd                        18..23
+0006 OpReturnValue                16..25
+";
+
+        assert_eq!(disassemble(&compile(input)), expected);
+    }
+
+    #[test]
+    fn invalid_instructions() {
+        let function = CompiledFunction {
+            instructions: vec![Opcode::Pop as u8, 255, Opcode::Constant as u8, 0],
+            ..Default::default()
+        };
+
+        assert_eq!(
+            disassemble_function(&function, &[]),
+            "0000 OpPop\n0001 <unknown opcode 255>\n0002 <truncated OpConstant>\n"
+        );
+    }
+}
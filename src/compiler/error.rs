@@ -0,0 +1,51 @@
+use std::fmt::Display;
+
+use crate::lexer::token::{Span, TokenKind};
+
+/// Errors found while compiling a program, the compiler rejects the whole program
+#[derive(Debug, Clone, PartialEq)]
+pub enum CompileError {
+    UnknownOperator {
+        operator: TokenKind,
+        span: Span,
+    },
+    /// The program has statements the parser could not make sense of
+    SyntaxError {
+        span: Span,
+    },
+    /// The program does not fit in the operands of the instructions
+    TooMany {
+        what: &'static str,
+        max: usize,
+        span: Span,
+    },
+}
+
+impl CompileError {
+    /// Location of the code that could not be compiled
+    pub fn span(&self) -> &Span {
+        match self {
+            CompileError::UnknownOperator { span, .. }
+            | CompileError::SyntaxError { span }
+            | CompileError::TooMany { span, .. } => span,
+        }
+    }
+}
+
+impl Display for CompileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CompileError::UnknownOperator { operator, .. } => {
+                write!(f, "unknown operator: {operator}")
+            }
+            CompileError::SyntaxError { .. } => {
+                write!(f, "cannot compile a statement with syntax errors")
+            }
+            CompileError::TooMany { what, max, .. } => {
+                write!(f, "too many {what}, the limit is {max}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for CompileError {}
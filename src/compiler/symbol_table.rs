@@ -0,0 +1,338 @@
+use std::{
+    collections::{HashMap, HashSet},
+    ops::Range,
+};
+
+/// Where the value of a name is stored when running the bytecode
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymbolScope {
+    /// A variable of the program, the builtin of the name is used while it is not set
+    Global,
+    /// A variable of the function being compiled, the ones of its for loops included
+    Local,
+    /// A variable of an enclosing function, captured by the closure
+    Free,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Symbol {
+    pub name: String,
+    pub scope: SymbolScope,
+    pub index: usize,
+}
+
+/// Code whose names a table holds, they are the scopes of the evaluator
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+enum ScopeKind {
+    /// The top level of the program, its names are globals
+    #[default]
+    Program,
+    Function,
+    /// The body of a for loop, its names are locals of the enclosing function and every iteration
+    /// gets new ones
+    Loop,
+}
+
+/// Names of a scope, the outermost table holds the globals while each function and each for loop
+/// being compiled gets its own enclosed table
+///
+/// Like in the evaluator, a name refers to the innermost variable that is set when the code runs,
+/// so a name may resolve to several variables that are tried in turn
+#[derive(Debug, Default)]
+pub struct SymbolTable {
+    outer: Option<Box<SymbolTable>>,
+    kind: ScopeKind,
+    store: HashMap<String, Symbol>,
+    /// Names that are always set when the code compiled from now on runs
+    set: HashSet<String>,
+    /// Slot of the first local of a loop, the loops continue the numbering of the enclosing scope
+    first_local: usize,
+    /// Number of if branches and while bodies around the code being compiled, their lets may
+    /// not run
+    branches: usize,
+    /// Slot of the next definition
+    pub num_definitions: usize,
+    /// Slots of the locals of the function, the ones of its loops included
+    pub num_locals: usize,
+    /// Symbols of the enclosing scopes this scope captures, in the order of their index
+    pub free_symbols: Vec<Symbol>,
+}
+
+impl SymbolTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Enters the body of a function
+    pub fn new_enclosed(outer: SymbolTable) -> Self {
+        Self {
+            outer: Some(Box::new(outer)),
+            kind: ScopeKind::Function,
+            ..Default::default()
+        }
+    }
+
+    /// Enters the body of a for loop
+    pub fn new_loop(outer: SymbolTable) -> Self {
+        let first_local = match outer.kind {
+            ScopeKind::Program => 0,
+            _ => outer.num_definitions,
+        };
+
+        Self {
+            outer: Some(Box::new(outer)),
+            kind: ScopeKind::Loop,
+            first_local,
+            num_definitions: first_local,
+            num_locals: first_local,
+            ..Default::default()
+        }
+    }
+
+    /// Leaves the scope, returns the enclosing one
+    pub fn into_outer(self) -> Option<SymbolTable> {
+        let mut outer = *self.outer?;
+        if self.kind == ScopeKind::Loop {
+            outer.num_locals = outer.num_locals.max(self.num_locals);
+        }
+        Some(outer)
+    }
+
+    /// Slots of the locals of a loop
+    pub fn loop_locals(&self) -> Range<usize> {
+        self.first_local..self.num_definitions
+    }
+
+    /// Defines a global or a local depending on the scope, defining a name again in the same
+    /// scope reuses its slot
+    pub fn define(&mut self, name: &str) -> Symbol {
+        if let Some(existing) = self.store.get(name) {
+            return existing.clone();
+        }
+
+        let scope = match self.kind {
+            ScopeKind::Program => SymbolScope::Global,
+            _ => SymbolScope::Local,
+        };
+        self.insert(name, scope)
+    }
+
+    /// Defines a parameter of the function owning the scope, each parameter gets its own slot
+    /// since the arguments are stored in order, the last parameter of a name is the one it refers
+    /// to
+    pub fn define_parameter(&mut self, name: &str) -> Symbol {
+        let symbol = self.insert(name, SymbolScope::Local);
+        self.set.insert(name.to_string());
+        symbol
+    }
+
+    /// Enters code that may not run, like the branches of an if or the body of a while loop
+    pub fn enter_branch(&mut self) {
+        self.branches += 1;
+    }
+
+    pub fn leave_branch(&mut self) {
+        self.branches -= 1;
+    }
+
+    /// Records that the variable the scope defines for the name is set, for the code compiled
+    /// from now on, unless the let is in a branch
+    pub fn mark_set(&mut self, name: &str) {
+        if self.branches == 0 && self.store.contains_key(name) {
+            self.set.insert(name.to_string());
+        }
+    }
+
+    /// Looks up a name from the innermost scope outwards, returns the variables it may refer to,
+    /// innermost first
+    ///
+    /// The list ends with a variable that is always set or with the global of the name, which is
+    /// defined if needed so a missing name is only reported when the code reading it runs, the
+    /// variables of the enclosing functions are turned into free symbols of this scope
+    pub fn resolve(&mut self, name: &str) -> Vec<Symbol> {
+        if self.outer.is_none() {
+            return vec![self.define(name)];
+        }
+
+        let mut symbols: Vec<Symbol> = self.store.get(name).cloned().into_iter().collect();
+        if !symbols.is_empty() && self.set.contains(name) {
+            return symbols;
+        }
+
+        let outer = self.outer.as_mut().expect("Checked above");
+        for symbol in outer.resolve(name) {
+            let symbol = match (self.kind, symbol.scope) {
+                (ScopeKind::Function, SymbolScope::Local | SymbolScope::Free) => {
+                    self.capture(symbol)
+                }
+                _ => symbol,
+            };
+            symbols.push(symbol);
+        }
+        symbols
+    }
+
+    /// Names of the globals in the order of their slots, on the outermost table
+    pub fn global_names(&self) -> Vec<String> {
+        let mut names = vec![String::new(); self.num_definitions];
+        for symbol in self.store.values() {
+            names[symbol.index] = symbol.name.clone();
+        }
+        names
+    }
+
+    fn capture(&mut self, original: Symbol) -> Symbol {
+        let index = match self.free_symbols.iter().position(|s| *s == original) {
+            Some(index) => index,
+            None => {
+                self.free_symbols.push(original.clone());
+                self.free_symbols.len() - 1
+            }
+        };
+
+        Symbol {
+            name: original.name,
+            scope: SymbolScope::Free,
+            index,
+        }
+    }
+
+    fn insert(&mut self, name: &str, scope: SymbolScope) -> Symbol {
+        let symbol = Symbol {
+            name: name.to_string(),
+            scope,
+            index: self.num_definitions,
+        };
+        self.num_definitions += 1;
+        if scope == SymbolScope::Local {
+            self.num_locals = self.num_locals.max(self.num_definitions);
+        }
+
+        self.store.insert(name.to_string(), symbol.clone());
+        symbol
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn symbol(name: &str, scope: SymbolScope, index: usize) -> Symbol {
+        Symbol {
+            name: name.to_string(),
+            scope,
+            index,
+        }
+    }
+
+    #[test]
+    fn define_and_resolve() {
+        let mut global = SymbolTable::new();
+        assert_eq!(global.define("a"), symbol("a", SymbolScope::Global, 0));
+        assert_eq!(global.define("b"), symbol("b", SymbolScope::Global, 1));
+        // Defining again reuses the slot
+        assert_eq!(global.define("a"), symbol("a", SymbolScope::Global, 0));
+
+        let mut first = SymbolTable::new_enclosed(global);
+        assert_eq!(
+            first.define_parameter("c"),
+            symbol("c", SymbolScope::Local, 0)
+        );
+        // Each parameter has its own slot, the last one wins
+        assert_eq!(
+            first.define_parameter("p"),
+            symbol("p", SymbolScope::Local, 1)
+        );
+        assert_eq!(
+            first.define_parameter("p"),
+            symbol("p", SymbolScope::Local, 2)
+        );
+        assert_eq!(first.resolve("p"), vec![symbol("p", SymbolScope::Local, 2)]);
+        first.define("d");
+
+        let mut second = SymbolTable::new_enclosed(first);
+        assert_eq!(
+            second.define_parameter("b"),
+            symbol("b", SymbolScope::Local, 0)
+        );
+
+        let cases = [
+            ("a", vec![symbol("a", SymbolScope::Global, 0)]),
+            ("b", vec![symbol("b", SymbolScope::Local, 0)]),
+            ("c", vec![symbol("c", SymbolScope::Free, 0)]),
+            // Not set yet, the global of the name is used until it is
+            (
+                "d",
+                vec![
+                    symbol("d", SymbolScope::Free, 1),
+                    symbol("d", SymbolScope::Global, 2),
+                ],
+            ),
+            // Defined as a global that is never set
+            ("len", vec![symbol("len", SymbolScope::Global, 3)]),
+        ];
+        for (name, expected) in cases {
+            assert_eq!(
+                second.resolve(name),
+                expected,
+                "Unexpected symbols for {name}"
+            );
+        }
+
+        // The free symbols point at the symbols of the enclosing scope
+        assert_eq!(
+            second.free_symbols,
+            vec![
+                symbol("c", SymbolScope::Local, 0),
+                symbol("d", SymbolScope::Local, 3)
+            ]
+        );
+
+        let first = second.into_outer().unwrap();
+        assert!(first.free_symbols.is_empty());
+        let global = first.into_outer().unwrap();
+        assert_eq!(global.global_names(), ["a", "b", "d", "len"]);
+    }
+
+    #[test]
+    fn locals_are_tried_until_set() {
+        let mut outer = SymbolTable::new_enclosed(SymbolTable::new());
+        outer.define("x");
+
+        let mut inner = SymbolTable::new_enclosed(outer);
+        inner.define("x");
+        assert_eq!(
+            inner.resolve("x"),
+            vec![
+                symbol("x", SymbolScope::Local, 0),
+                symbol("x", SymbolScope::Free, 0),
+                symbol("x", SymbolScope::Global, 0),
+            ]
+        );
+
+        // Once the let has run the enclosing variables are hidden
+        inner.mark_set("x");
+        assert_eq!(inner.resolve("x"), vec![symbol("x", SymbolScope::Local, 0)]);
+    }
+
+    #[test]
+    fn loops() {
+        let mut function = SymbolTable::new_enclosed(SymbolTable::new());
+        function.define_parameter("a");
+
+        let mut body = SymbolTable::new_loop(function);
+        assert_eq!(body.define("i"), symbol("i", SymbolScope::Local, 1));
+        assert_eq!(body.define("b"), symbol("b", SymbolScope::Local, 2));
+        assert_eq!(body.loop_locals(), 1..3);
+        // The locals of the enclosing function are not captured
+        assert_eq!(body.resolve("a"), vec![symbol("a", SymbolScope::Local, 0)]);
+
+        let function = body.into_outer().unwrap();
+        assert_eq!(function.num_definitions, 1);
+        assert_eq!(function.num_locals, 3);
+
+        // The loops of the program start at the first local of main
+        let body = SymbolTable::new_loop(SymbolTable::new());
+        assert_eq!(body.loop_locals(), 0..0);
+    }
+}
@@ -0,0 +1,403 @@
+use crate::{
+    compiler::error::CompileError,
+    lexer::{
+        error::LexError,
+        token::{Span, TokenKind},
+    },
+    parser::{MAX_NESTING, error::ParseError},
+    source_map::SourceFile,
+    vm::error::RuntimeError,
+};
+
+/// Spans covering more lines than this only show their first and last lines
+const MAX_SPAN_LINES: usize = 6;
+
+/// An error about a location of the source, rendered the way rustc does with the offending
+/// lines and the span underlined
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub code: Option<&'static str>,
+    pub message: String,
+    pub span: Span,
+    /// Short text printed next to the underline
+    pub label: Option<String>,
+    /// Help notes printed after the source snippet
+    pub notes: Vec<String>,
+}
+
+impl Diagnostic {
+    pub fn error(message: impl Into<String>, span: Span) -> Self {
+        Self {
+            code: None,
+            message: message.into(),
+            span,
+            label: None,
+            notes: vec![],
+        }
+    }
+
+    pub fn with_code(mut self, code: &'static str) -> Self {
+        self.code = Some(code);
+        self
+    }
+
+    pub fn with_label(mut self, label: impl Into<String>) -> Self {
+        self.label = Some(label.into());
+        self
+    }
+
+    pub fn with_help(mut self, help: impl Into<String>) -> Self {
+        self.notes.push(help.into());
+        self
+    }
+
+    /// Renders the diagnostic for the file the span belongs to
+    pub fn render(&self, file: &SourceFile) -> String {
+        let start = file.position(self.span.start);
+        let (start_line, start_col) = (start.line, start.column);
+        // The end is exclusive, point at the start of the last character of the span unless it is
+        // empty, the character before the end may take several bytes
+        let last = file
+            .source()
+            .get(self.span.start..self.span.end.min(file.source().len()))
+            .and_then(|text| text.char_indices().next_back())
+            .map_or(self.span.start, |(i, _)| self.span.start + i);
+        let end = file.position(last);
+        let (end_line, end_col) = (end.line, end.column);
+
+        let gutter = end_line.to_string().len();
+        let pad = " ".repeat(gutter);
+        let label = self
+            .label
+            .as_ref()
+            .map(|l| format!(" {l}"))
+            .unwrap_or_default();
+
+        let mut out = match self.code {
+            Some(code) => format!("error[{code}]: {}\n", self.message),
+            None => format!("error: {}\n", self.message),
+        };
+        out += &format!("{pad}--> {}:{start}\n", file.name());
+        out += &format!("{pad} |\n");
+
+        if start_line == end_line {
+            let width = (end_col + 1).saturating_sub(start_col).max(1);
+            out += &format!("{start_line:>gutter$} | {}\n", file.line_text(start_line));
+            out += &format!(
+                "{pad} | {}{}{label}\n",
+                " ".repeat(start_col - 1),
+                "^".repeat(width)
+            );
+        } else {
+            out += &format!("{start_line:>gutter$} |   {}\n", file.line_text(start_line));
+            out += &format!("{pad} |  {}^\n", "_".repeat(start_col));
+
+            for line in start_line + 1..=end_line {
+                let elided = end_line - start_line + 1 > MAX_SPAN_LINES
+                    && line > start_line + 1
+                    && line < end_line - 1;
+                if elided {
+                    // Only print the marker once
+                    if line == start_line + 2 {
+                        out += "...\n";
+                    }
+                    continue;
+                }
+                out += &format!("{line:>gutter$} | | {}\n", file.line_text(line));
+            }
+
+            out += &format!("{pad} | |{}^{label}\n", "_".repeat(end_col));
+        }
+
+        if !self.notes.is_empty() {
+            out += &format!("{pad} |\n");
+            for note in self.notes.iter() {
+                out += &format!("{pad} = help: {note}\n");
+            }
+        }
+
+        out
+    }
+}
+
+impl From<&ParseError> for Diagnostic {
+    fn from(err: &ParseError) -> Self {
+        let diagnostic =
+            Diagnostic::error(err.to_string(), err.span().clone()).with_code(err.code());
+
+        let diagnostic = match err {
+            ParseError::UnexpectedToken { expected, .. } => {
+                let expected = expected
+                    .iter()
+                    .map(|kind| format!("`{kind}`"))
+                    .collect::<Vec<String>>()
+                    .join(" or ");
+                diagnostic.with_label(format!("expected {expected}"))
+            }
+            ParseError::ExpectedExpression { .. } => diagnostic.with_label("expected expression"),
+            ParseError::ExpectedIdentifier { .. } => diagnostic.with_label("expected identifier"),
+            ParseError::InvalidAssignmentTarget { .. } => diagnostic
+                .with_label("cannot assign to the left side")
+                .with_help("only variables and indexes like a[0] can be assigned"),
+            ParseError::TooDeeplyNested { .. } => diagnostic
+                .with_label("nested too deeply")
+                .with_help(format!(
+                    "expressions and blocks can be nested at most {MAX_NESTING} levels deep"
+                )),
+            ParseError::IllegalToken { found } => match &found.kind {
+                TokenKind::Illegal(LexError::UnterminatedString) => diagnostic
+                    .with_label("unterminated string")
+                    .with_help("add the closing `\"`"),
+                TokenKind::Illegal(LexError::InvalidEscape) => diagnostic
+                    .with_label("in this string")
+                    .with_help("valid escapes are \\n, \\t, \\r, \\0, \\\", \\\\ and \\u{...}"),
+                TokenKind::Illegal(LexError::UnterminatedComment) => diagnostic
+                    .with_label("unterminated comment")
+                    .with_help("every `/*` needs its own `*/`, block comments can be nested"),
+                TokenKind::Illegal(LexError::IntegerOverflow) => diagnostic
+                    .with_label("out of range")
+                    .with_help(format!("the largest integer is {}", i64::MAX)),
+                TokenKind::Illegal(LexError::FloatOverflow) => diagnostic
+                    .with_label("out of range")
+                    .with_help(format!("the largest float is {:e}", f64::MAX)),
+                TokenKind::Illegal(LexError::InvalidDigit { .. }) => {
+                    diagnostic.with_label("in this literal")
+                }
+                TokenKind::Illegal(LexError::MissingDigits) => {
+                    diagnostic.with_label("expected digits after the prefix")
+                }
+                _ => diagnostic
+                    .with_label("not recognized")
+                    .with_help("remove this character"),
+            },
+        };
+
+        if err.found().kind == TokenKind::Eof {
+            diagnostic.with_help("the input ended before the statement was complete")
+        } else {
+            diagnostic
+        }
+    }
+}
+
+impl From<&CompileError> for Diagnostic {
+    fn from(err: &CompileError) -> Self {
+        Diagnostic::error(err.to_string(), err.span().clone())
+    }
+}
+
+impl From<&RuntimeError> for Diagnostic {
+    fn from(err: &RuntimeError) -> Self {
+        let diagnostic = Diagnostic::error(err.to_string(), err.span().clone());
+
+        match err {
+            RuntimeError::StackOverflow { .. } => diagnostic
+                .with_label("in this call")
+                .with_help("check that the recursion ends"),
+            RuntimeError::Failed { .. } => diagnostic,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        compiler::Compiler,
+        lexer::Lexer,
+        parser::{
+            Parser,
+            ast::{Expression, Statement},
+        },
+    };
+
+    fn render_first_error(source: &str) -> String {
+        let mut parser = Parser::new(Lexer::new(source));
+        let errs = parser.parse_program().unwrap_err();
+        Diagnostic::from(&errs[0]).render(&SourceFile::new("test.mk", source))
+    }
+
+    #[test]
+    fn single_line() {
+        let expected = "\
+error[E0001]: expected `=`, found `5`
+ --> test.mk:2:7
+  |
+2 | let x 5;
+  |       ^ expected `=`
+";
+        assert_eq!(render_first_error("let a = 1;\nlet x 5;"), expected);
+    }
+
+    #[test]
+    fn end_of_input() {
+        let expected = "\
+error[E0001]: expected `,` or `)`, found end of input
+ --> test.mk:1:9
+  |
+1 | add(1, 2
+  |         ^ expected `,` or `)`
+  |
+  = help: the input ended before the statement was complete
+";
+        assert_eq!(render_first_error("add(1, 2"), expected);
+    }
+
+    #[test]
+    fn lexer_error() {
+        let expected = "\
+error[E0004]: unterminated block comment
+ --> test.mk:1:5
+  |
+1 | 1 + /* two /* three */
+  |     ^^^^^^^^^^^^^^^^^^ unterminated comment
+  |
+  = help: every `/*` needs its own `*/`, block comments can be nested
+";
+        assert_eq!(render_first_error("1 + /* two /* three */"), expected);
+    }
+
+    #[test]
+    fn compile_error() {
+        let call = format!("len({})", vec!["1"; 256].join(","));
+        let source = format!("let a = 1;\n{call}");
+        let program = Parser::new(Lexer::new(&source)).parse_program().unwrap();
+        let err = Compiler::new().compile(&program).unwrap_err();
+
+        let expected = format!(
+            "\
+error: too many arguments, the limit is 255
+ --> test.mk:2:1
+  |
+2 | {call}
+  | {}
+",
+            "^".repeat(call.len())
+        );
+        assert_eq!(
+            Diagnostic::from(&err).render(&SourceFile::new("test.mk", source)),
+            expected
+        );
+    }
+
+    #[test]
+    fn multibyte_end() {
+        let cases = [
+            ("1 + €", Span { start: 4, end: 7 }, "    ^"),
+            (
+                "let s = \"café\";",
+                Span { start: 8, end: 15 },
+                "        ^^^^^^",
+            ),
+        ];
+
+        for (source, span, underline) in cases {
+            let diagnostic = Diagnostic::error("error", span);
+            let expected = format!(
+                "error: error\n --> main.mk:1:{}\n  |\n1 | {source}\n  | {underline}\n",
+                underline.len() - underline.trim_start().len() + 1
+            );
+            assert_eq!(
+                diagnostic.render(&SourceFile::new("main.mk", source)),
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn wide_span() {
+        let source = "let value = foo + bar;";
+        let diagnostic =
+            Diagnostic::error("identifier not found: foo", Span { start: 12, end: 21 })
+                .with_label("in this expression")
+                .with_help("declare it with a let statement");
+
+        let expected = "\
+error: identifier not found: foo
+ --> main.mk:1:13
+  |
+1 | let value = foo + bar;
+  |             ^^^^^^^^^ in this expression
+  |
+  = help: declare it with a let statement
+";
+        assert_eq!(
+            diagnostic.render(&SourceFile::new("main.mk", source)),
+            expected
+        );
+    }
+
+    #[test]
+    fn multi_line_span() {
+        let source = "let f = fn(x) {\n  let y = x;\n  y\n};";
+        let diagnostic = Diagnostic::error("unused function", Span { start: 8, end: 34 })
+            .with_label("this block");
+
+        let expected = "\
+error: unused function
+ --> main.mk:1:9
+  |
+1 |   let f = fn(x) {
+  |  _________^
+2 | |   let y = x;
+3 | |   y
+4 | | };
+  | |_^ this block
+";
+        assert_eq!(
+            diagnostic.render(&SourceFile::new("main.mk", source)),
+            expected
+        );
+    }
+
+    #[test]
+    fn long_multi_line_span() {
+        let source = "{\n1\n2\n3\n4\n5\n6\n7\n}";
+        let diagnostic = Diagnostic::error("long", Span { start: 0, end: 17 });
+
+        let expected = "\
+error: long
+ --> main.mk:1:1
+  |
+1 |   {
+  |  _^
+2 | | 1
+...
+8 | | 7
+9 | | }
+  | |_^
+";
+        assert_eq!(
+            diagnostic.render(&SourceFile::new("main.mk", source)),
+            expected
+        );
+    }
+
+    #[test]
+    fn block_statement_span() {
+        let source = "if (ok) {\n  puts(1);\n}";
+        let program = Parser::new(Lexer::new(source)).parse_program().unwrap();
+        let Statement::Expression(Expression::If(if_exp)) = &program.statements[0] else {
+            panic!("Expected an if expression");
+        };
+
+        let diagnostic =
+            Diagnostic::error("condition is never false", if_exp.consequence.span.clone())
+                .with_label("this block always runs");
+
+        let expected = "\
+error: condition is never false
+ --> main.mk:1:9
+  |
+1 |   if (ok) {
+  |  _________^
+2 | |   puts(1);
+3 | | }
+  | |_^ this block always runs
+";
+        assert_eq!(
+            diagnostic.render(&SourceFile::new("main.mk", source)),
+            expected
+        );
+    }
+}
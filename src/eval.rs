@@ -0,0 +1,779 @@
+mod eval_test;
+
+use std::rc::Rc;
+
+use crate::{
+    lexer::token::TokenKind,
+    object::{
+        Function, Hash, Object,
+        environment::{Env, Environment},
+    },
+    parser::ast::{
+        AssignExp, BlockStatement, Expression, For, HashLiteral, Ident, IfExp, Literal, Program,
+        RangeExp, SliceExp, Statement, While,
+    },
+    vm::MAX_FRAMES,
+};
+
+/// Native stack the evaluator needs to nest calls up to the limit, debug builds use about 10 KiB
+/// per call so the main thread is not enough
+pub const STACK_SIZE: usize = 64 << 20;
+
+/// Entry point of the evaluator, evaluates every statement of the program and returns the value
+/// of the last one, or the value of the first return statement or error found
+pub fn eval_program(program: &Program, env: &Env) -> Object {
+    let mut result = Object::Null;
+
+    for stmt in program.statements.iter() {
+        result = eval_statement(stmt, env);
+
+        match result {
+            // A return at the top level stops the program, unwrap it since nobody is above to
+            // receive it
+            Object::ReturnValue(value) => return *value,
+            Object::Error(_) => return result,
+            Object::Break | Object::Continue => return loop_control_error(&result),
+            _ => {}
+        }
+    }
+
+    result
+}
+
+/// Evaluates a block statement, unlike the program, return values are kept wrapped so they can
+/// bubble up through nested blocks until they reach the top level
+fn eval_block_statement(block: &BlockStatement, env: &Env) -> Object {
+    let mut result = Object::Null;
+
+    for stmt in block.statements.iter() {
+        result = eval_statement(stmt, env);
+
+        if let Object::ReturnValue(_) | Object::Error(_) | Object::Break | Object::Continue = result
+        {
+            return result;
+        }
+    }
+
+    result
+}
+
+/// Evaluates a single statement
+fn eval_statement(stmt: &Statement, env: &Env) -> Object {
+    match stmt {
+        Statement::Expression(exp) => eval_expression(exp, env),
+        Statement::Return(ret) => {
+            let value = match &ret.exp {
+                Some(exp) => eval_expression(exp, env),
+                None => Object::Null,
+            };
+            if value.is_error() {
+                return value;
+            }
+            Object::ReturnValue(Box::new(value))
+        }
+        Statement::Let(let_stmt) => {
+            let value = eval_expression(&let_stmt.exp, env);
+            if value.is_error() {
+                return value;
+            }
+
+            if let TokenKind::Ident { name } = &let_stmt.identifier.kind {
+                env.borrow_mut().set(name, value);
+            }
+            Object::Null
+        }
+        Statement::While(while_stmt) => eval_while_statement(while_stmt, env),
+        Statement::For(for_stmt) => eval_for_statement(for_stmt, env),
+        Statement::Break(_) => Object::Break,
+        Statement::Continue(_) => Object::Continue,
+        Statement::Error(_) => {
+            Object::Error("cannot evaluate a statement with syntax errors".to_string())
+        }
+    }
+}
+
+/// Evaluates an expression
+fn eval_expression(exp: &Expression, env: &Env) -> Object {
+    match exp {
+        Expression::Literal(Literal::Integer(int)) => Object::Integer(int.value),
+        Expression::Literal(Literal::Float(float)) => Object::Float(float.value),
+        Expression::Literal(Literal::Boolean(boolean)) => Object::Boolean(boolean.value),
+        Expression::Literal(Literal::String(string)) => Object::String(string.value.clone()),
+        Expression::Prefix(prefix) => {
+            let right = eval_expression(&prefix.right, env);
+            if right.is_error() {
+                return right;
+            }
+            eval_prefix_expression(&prefix.operator.kind, right)
+        }
+        Expression::Infix(infix) => {
+            let left = eval_expression(&infix.left, env);
+            if left.is_error() {
+                return left;
+            }
+            if matches!(infix.operator.kind, TokenKind::And | TokenKind::Or) {
+                return eval_logical_expression(&infix.operator.kind, left, &infix.right, env);
+            }
+            let right = eval_expression(&infix.right, env);
+            if right.is_error() {
+                return right;
+            }
+            eval_infix_expression(&infix.operator.kind, left, right)
+        }
+        Expression::If(if_exp) => eval_if_expression(if_exp, env),
+        Expression::Identifier(ident) => eval_identifier(ident, env),
+        Expression::Function(function) => Object::Function(Function {
+            parameters: function.parameters.clone(),
+            body: function.body.clone(),
+            env: env.clone(),
+        }),
+        Expression::Call(call) => {
+            let function = eval_expression(&call.function, env);
+            if function.is_error() {
+                return function;
+            }
+
+            let args = match eval_expressions(&call.arguments, env) {
+                Ok(args) => args,
+                Err(err) => return err,
+            };
+
+            apply_function(function, args, env)
+        }
+        Expression::Array(array) => match eval_expressions(&array.elements, env) {
+            Ok(elements) => Object::Array(Rc::new(elements)),
+            Err(err) => err,
+        },
+        Expression::Index(index_exp) => {
+            let left = eval_expression(&index_exp.left, env);
+            if left.is_error() {
+                return left;
+            }
+            let index = eval_expression(&index_exp.index, env);
+            if index.is_error() {
+                return index;
+            }
+            eval_index_expression(left, index)
+        }
+        Expression::Slice(slice) => eval_slice_expression(slice, env),
+        Expression::Hash(hash) => eval_hash_literal(hash, env),
+        Expression::Range(range) => eval_range_expression(range, env),
+        Expression::Assign(assign) => eval_assign_expression(assign, env),
+    }
+}
+
+/// Evaluates a hash literal, keys and values are evaluated in the order they are written
+fn eval_hash_literal(hash_lit: &HashLiteral, env: &Env) -> Object {
+    let mut hash = Hash::new();
+
+    for (key_exp, value_exp) in hash_lit.pairs.iter() {
+        let key = eval_expression(key_exp, env);
+        if key.is_error() {
+            return key;
+        }
+        let Some(key) = key.hash_key() else {
+            return Object::Error(format!("unusable as hash key: {}", key.type_name()));
+        };
+
+        let value = eval_expression(value_exp, env);
+        if value.is_error() {
+            return value;
+        }
+
+        hash.insert(key, value);
+    }
+
+    Object::Hash(Rc::new(hash))
+}
+
+/// Evaluates an index expression, negative indices count from the end and indices out of range
+/// or missing keys yield null
+pub(crate) fn eval_index_expression(left: Object, index: Object) -> Object {
+    match (left, index) {
+        (Object::Array(elements), Object::Integer(index)) => {
+            match resolve_index(index, elements.len()) {
+                Some(i) if i < elements.len() => elements[i].clone(),
+                _ => Object::Null,
+            }
+        }
+        (Object::Array(_), index) => Object::Error(format!(
+            "array index must be INTEGER, got {}",
+            index.type_name()
+        )),
+        (Object::Hash(hash), key) => match key.hash_key() {
+            Some(key) => hash.get(&key).cloned().unwrap_or(Object::Null),
+            None => Object::Error(format!("unusable as hash key: {}", key.type_name())),
+        },
+        (left, _) => Object::Error(format!(
+            "index operator not supported: {}",
+            left.type_name()
+        )),
+    }
+}
+
+/// Evaluates a slice expression, bounds are clamped to the collection so it never fails because
+/// of them
+fn eval_slice_expression(slice: &SliceExp, env: &Env) -> Object {
+    let left = eval_expression(&slice.left, env);
+    if left.is_error() {
+        return left;
+    }
+
+    // Each operand is checked before the next one is evaluated
+    if let Err(err) = slice_elements(&left) {
+        return err;
+    }
+
+    let mut bounds = [None, None];
+    for (bound, exp) in bounds.iter_mut().zip([&slice.start, &slice.end]) {
+        if let Some(exp) = exp {
+            let value = eval_expression(exp, env);
+            if value.is_error() {
+                return value;
+            }
+            if let Err(err) = slice_bound(&value) {
+                return err;
+            }
+            *bound = Some(value);
+        }
+    }
+
+    slice_array(left, bounds)
+}
+
+/// Slices an array between the given bounds, a missing bound is the start or the end of the array
+pub(crate) fn slice_array(left: Object, bounds: [Option<Object>; 2]) -> Object {
+    let elements = match slice_elements(&left) {
+        Ok(elements) => elements,
+        Err(err) => return err,
+    };
+
+    let len = elements.len();
+    let mut range = [0, len];
+    for (offset, bound) in range.iter_mut().zip(bounds) {
+        if let Some(bound) = bound {
+            match slice_bound(&bound) {
+                Ok(value) => *offset = resolve_index(value, len).unwrap_or(0).min(len),
+                Err(err) => return err,
+            }
+        }
+    }
+
+    let [start, end] = range;
+    if start >= end {
+        return Object::Array(Rc::new(vec![]));
+    }
+    Object::Array(Rc::new(elements[start..end].to_vec()))
+}
+
+/// Returns the elements of the value being sliced, only arrays can be
+pub(crate) fn slice_elements(left: &Object) -> Result<&[Object], Object> {
+    match left {
+        Object::Array(elements) => Ok(elements),
+        _ => Err(Object::Error(format!(
+            "slice operator not supported: {}",
+            left.type_name()
+        ))),
+    }
+}
+
+/// Returns the value of a bound of a slice, it must be an integer
+pub(crate) fn slice_bound(bound: &Object) -> Result<i64, Object> {
+    match bound {
+        Object::Integer(value) => Ok(*value),
+        _ => Err(Object::Error(format!(
+            "slice bounds must be INTEGER, got {}",
+            bound.type_name()
+        ))),
+    }
+}
+
+/// Turns a possibly negative index into an offset from the start of a collection of the given
+/// length, returns None if a negative index goes past the start
+fn resolve_index(index: i64, len: usize) -> Option<usize> {
+    if index < 0 {
+        len.checked_sub(index.unsigned_abs() as usize)
+    } else {
+        Some(index as usize)
+    }
+}
+
+/// Evaluates a list of expressions from left to right, stops at the first error
+fn eval_expressions(exps: &[Expression], env: &Env) -> Result<Vec<Object>, Object> {
+    let mut values = Vec::with_capacity(exps.len());
+
+    for exp in exps.iter() {
+        let value = eval_expression(exp, env);
+        if value.is_error() {
+            return Err(value);
+        }
+        values.push(value);
+    }
+
+    Ok(values)
+}
+
+/// Calls a function with the given arguments, the body runs in a new scope enclosed by the one
+/// the function was defined in
+///
+/// Like the frames of the vm, the program counts as a call so the calls nest up to one less than
+/// the limit, deeper ones fail instead of overflowing the native stack
+fn apply_function(function: Object, args: Vec<Object>, env: &Env) -> Object {
+    let function = match function {
+        Object::Function(function) => function,
+        Object::Builtin(builtin) => {
+            return builtin
+                .call(&args)
+                .unwrap_or_else(|err| Object::Error(err.to_string()));
+        }
+        obj => return Object::Error(format!("not a function: {}", obj.type_name())),
+    };
+
+    if function.parameters.len() != args.len() {
+        return Object::Error(format!(
+            "wrong number of arguments: want={}, got={}",
+            function.parameters.len(),
+            args.len()
+        ));
+    }
+
+    let mut scope = Environment::new_call(function.env.clone(), env);
+    if scope.depth() >= MAX_FRAMES {
+        return Object::Error("stack overflow".to_string());
+    }
+    for (param, arg) in function.parameters.iter().zip(args) {
+        scope.set(&param.name, arg);
+    }
+
+    // Unwrap the return value, otherwise it would keep bubbling up and stop the caller too
+    match eval_block_statement(&function.body, &scope.into_env()) {
+        Object::ReturnValue(value) => *value,
+        // Loops do not reach through function calls
+        result @ (Object::Break | Object::Continue) => loop_control_error(&result),
+        result => result,
+    }
+}
+
+/// Error for a break or continue that did not find a loop to stop
+fn loop_control_error(signal: &Object) -> Object {
+    Object::Error(format!("{signal} outside of a loop"))
+}
+
+/// Runs the body while the condition is truthy, loops have no value
+fn eval_while_statement(while_stmt: &While, env: &Env) -> Object {
+    loop {
+        let cond = eval_expression(&while_stmt.cond, env);
+        if cond.is_error() {
+            return cond;
+        }
+        if !cond.is_truthy() {
+            return Object::Null;
+        }
+
+        match eval_block_statement(&while_stmt.body, env) {
+            Object::Break => return Object::Null,
+            result @ (Object::ReturnValue(_) | Object::Error(_)) => return result,
+            _ => {}
+        }
+    }
+}
+
+/// Runs the body once per item of the iterable, each iteration gets its own scope with the loop
+/// variable so closures capture the value of their iteration
+fn eval_for_statement(for_stmt: &For, env: &Env) -> Object {
+    let iterable = eval_expression(&for_stmt.iterable, env);
+    if iterable.is_error() {
+        return iterable;
+    }
+
+    let items: Box<dyn Iterator<Item = Object>> = match into_iterable(iterable) {
+        Object::Array(elements) => Box::new((0..elements.len()).map(move |i| elements[i].clone())),
+        Object::Range(start, end) => Box::new((start..end).map(Object::Integer)),
+        err => return err,
+    };
+
+    for item in items {
+        let mut scope = Environment::new_enclosed(env.clone());
+        scope.set(&for_stmt.variable.name, item);
+
+        match eval_block_statement(&for_stmt.body, &scope.into_env()) {
+            Object::Break => break,
+            result @ (Object::ReturnValue(_) | Object::Error(_)) => return result,
+            _ => {}
+        }
+    }
+
+    Object::Null
+}
+
+/// Returns the array or the range a for loop goes through, strings are iterated by chars and
+/// hashes by keys
+/// Ranges are not materialized so they can be as long as needed
+pub(crate) fn into_iterable(obj: Object) -> Object {
+    match obj {
+        Object::Array(_) | Object::Range(..) => obj,
+        Object::String(s) => Object::Array(Rc::new(
+            s.chars().map(|c| Object::String(c.to_string())).collect(),
+        )),
+        Object::Hash(hash) => Object::Array(Rc::new(
+            hash.iter()
+                .map(|(key, _)| Object::from(key.clone()))
+                .collect(),
+        )),
+        obj => Object::Error(format!("iteration not supported: {}", obj.type_name())),
+    }
+}
+
+/// Evaluates an assignment, its value is the assigned one
+fn eval_assign_expression(assign: &AssignExp, env: &Env) -> Object {
+    // Checked first so a compound assignment does not report the variable as not found
+    if let Expression::Identifier(ident) = assign.target.as_ref()
+        && env.borrow().get(&ident.name).is_none()
+    {
+        return undeclared_error(ident);
+    }
+
+    // The indexes of the target are evaluated once, before the value, the same ones are used to
+    // read the current value and to store the new one
+    let (root, indexes) = match eval_assignment_place(&assign.target, env) {
+        Ok(place) => place,
+        Err(err) => return err,
+    };
+
+    let value = match assign.operator.kind.compound_operator() {
+        Some(operator) => {
+            let current = index_path(eval_identifier(root, env), &indexes);
+            if current.is_error() {
+                return current;
+            }
+            let value = eval_expression(&assign.value, env);
+            if value.is_error() {
+                return value;
+            }
+            eval_infix_expression(&operator, current, value)
+        }
+        None => eval_expression(&assign.value, env),
+    };
+    if value.is_error() {
+        return value;
+    }
+
+    match assign_to(root, &indexes, value.clone(), env) {
+        Some(err) => err,
+        None => value,
+    }
+}
+
+/// Splits the target of an assignment into its variable and its evaluated indexes, outermost
+/// first
+fn eval_assignment_place<'a>(
+    target: &'a Expression,
+    env: &Env,
+) -> Result<(&'a Ident, Vec<Object>), Object> {
+    match target {
+        Expression::Identifier(ident) => Ok((ident, vec![])),
+        Expression::Index(index_exp) => {
+            let (root, mut indexes) = eval_assignment_place(&index_exp.left, env)?;
+            let index = eval_expression(&index_exp.index, env);
+            if index.is_error() {
+                return Err(index);
+            }
+            indexes.push(index);
+            Ok((root, indexes))
+        }
+        // The parser only accepts variables and indexes
+        _ => Err(Object::Error(format!(
+            "invalid assignment target: {target}"
+        ))),
+    }
+}
+
+/// Stores the value at the end of the indexes of the variable, collections are values so
+/// assigning an element builds an updated copy of the collection that is then stored
+/// Returns the error if the value could not be stored
+fn assign_to(root: &Ident, indexes: &[Object], value: Object, env: &Env) -> Option<Object> {
+    let value = if indexes.is_empty() {
+        value
+    } else {
+        let current = eval_identifier(root, env);
+        if current.is_error() {
+            return Some(current);
+        }
+        match set_path(current, indexes, value) {
+            Ok(updated) => updated,
+            Err(err) => return Some(err),
+        }
+    };
+
+    if env.borrow_mut().assign(&root.name, value) {
+        None
+    } else {
+        Some(undeclared_error(root))
+    }
+}
+
+/// Indexes the value with each index in turn, like the target of an assignment does
+pub(crate) fn index_path(mut value: Object, indexes: &[Object]) -> Object {
+    for index in indexes {
+        value = eval_index_expression(value, index.clone());
+        if value.is_error() {
+            return value;
+        }
+    }
+    value
+}
+
+/// Returns a copy of the collection with the value stored at the end of the indexes, each
+/// collection along the way is updated
+pub(crate) fn set_path(root: Object, indexes: &[Object], value: Object) -> Result<Object, Object> {
+    let Some((last, rest)) = indexes.split_last() else {
+        return Ok(value);
+    };
+
+    let container = index_path(root.clone(), rest);
+    if container.is_error() {
+        return Err(container);
+    }
+    let updated = set_index(container, last.clone(), value)?;
+    set_path(root, rest, updated)
+}
+
+/// Returns a copy of the collection with the value stored at the index, the elements are only
+/// copied if the collection is shared
+pub(crate) fn set_index(container: Object, index: Object, value: Object) -> Result<Object, Object> {
+    match (container, index) {
+        (Object::Array(mut elements), Object::Integer(index)) => {
+            match resolve_index(index, elements.len()) {
+                Some(i) if i < elements.len() => Rc::make_mut(&mut elements)[i] = value,
+                _ => return Err(Object::Error(format!("index out of range: {index}"))),
+            }
+            Ok(Object::Array(elements))
+        }
+        (Object::Array(_), index) => Err(Object::Error(format!(
+            "array index must be INTEGER, got {}",
+            index.type_name()
+        ))),
+        (Object::Hash(mut hash), key) => match key.hash_key() {
+            Some(key) => {
+                Rc::make_mut(&mut hash).insert(key, value);
+                Ok(Object::Hash(hash))
+            }
+            None => Err(Object::Error(format!(
+                "unusable as hash key: {}",
+                key.type_name()
+            ))),
+        },
+        (container, _) => Err(Object::Error(format!(
+            "index assignment not supported: {}",
+            container.type_name()
+        ))),
+    }
+}
+
+fn undeclared_error(ident: &Ident) -> Object {
+    Object::Error(format!("assignment to undeclared variable: {}", ident.name))
+}
+
+/// Evaluates a range expression
+fn eval_range_expression(range: &RangeExp, env: &Env) -> Object {
+    let start = eval_expression(&range.start, env);
+    if start.is_error() {
+        return start;
+    }
+    // The start is checked before the end is evaluated
+    if let Err(err) = range_bound(&start) {
+        return err;
+    }
+    let end = eval_expression(&range.end, env);
+    if end.is_error() {
+        return end;
+    }
+
+    new_range(start, end)
+}
+
+/// Builds a range, both bounds must be integers
+pub(crate) fn new_range(start: Object, end: Object) -> Object {
+    match (range_bound(&start), range_bound(&end)) {
+        (Ok(start), Ok(end)) => Object::Range(start, end),
+        (Err(err), _) | (_, Err(err)) => err,
+    }
+}
+
+/// Returns the value of a bound of a range, it must be an integer
+pub(crate) fn range_bound(bound: &Object) -> Result<i64, Object> {
+    match bound {
+        Object::Integer(value) => Ok(*value),
+        _ => Err(Object::Error(format!(
+            "range bounds must be INTEGER, got {}",
+            bound.type_name()
+        ))),
+    }
+}
+
+/// Resolves an identifier from the innermost scope outwards, if no scope binds it, it is looked
+/// up in the builtins
+fn eval_identifier(ident: &Ident, env: &Env) -> Object {
+    let env = env.borrow();
+    match env.get(&ident.name).or_else(|| env.builtin(&ident.name)) {
+        Some(obj) => obj,
+        None => Object::Error(format!("identifier not found: {}", ident.name)),
+    }
+}
+
+/// Evaluates prefix operators
+pub(crate) fn eval_prefix_expression(operator: &TokenKind, right: Object) -> Object {
+    match (operator, right) {
+        (TokenKind::Bang, right) => Object::Boolean(!right.is_truthy()),
+        (TokenKind::Minus, Object::Integer(value)) => Object::Integer(value.wrapping_neg()),
+        (TokenKind::Minus, Object::Float(value)) => Object::Float(-value),
+        (TokenKind::Tilde, Object::Integer(value)) => Object::Integer(!value),
+        (_, right) => Object::Error(format!(
+            "unknown operator: {}{}",
+            operator,
+            right.type_name()
+        )),
+    }
+}
+
+/// Evaluates infix operators
+pub(crate) fn eval_infix_expression(operator: &TokenKind, left: Object, right: Object) -> Object {
+    match (left, right) {
+        (Object::Integer(left), Object::Integer(right)) => {
+            eval_integer_infix_expression(operator, left, right)
+        }
+        // Integers are promoted to floats when mixed with them
+        (Object::Float(left), Object::Float(right)) => {
+            eval_float_infix_expression(operator, left, right)
+        }
+        (Object::Integer(left), Object::Float(right)) => {
+            eval_float_infix_expression(operator, left as f64, right)
+        }
+        (Object::Float(left), Object::Integer(right)) => {
+            eval_float_infix_expression(operator, left, right as f64)
+        }
+        (Object::Boolean(left), Object::Boolean(right))
+            if matches!(operator, TokenKind::Eq | TokenKind::NotEq) =>
+        {
+            eval_equality(operator, left == right)
+        }
+        (Object::String(left), Object::String(right)) => {
+            eval_string_infix_expression(operator, left, right)
+        }
+        (left, right) if left.type_name() != right.type_name() => Object::Error(format!(
+            "type mismatch: {} {} {}",
+            left.type_name(),
+            operator,
+            right.type_name()
+        )),
+        (left, right) => Object::Error(format!(
+            "unknown operator: {} {} {}",
+            left.type_name(),
+            operator,
+            right.type_name()
+        )),
+    }
+}
+
+/// Evaluates `&&` and `||`, the right operand is only evaluated when the left one does not
+/// decide the result
+fn eval_logical_expression(
+    operator: &TokenKind,
+    left: Object,
+    right: &Expression,
+    env: &Env,
+) -> Object {
+    match (operator, left.is_truthy()) {
+        (TokenKind::And, false) => return Object::Boolean(false),
+        (TokenKind::Or, true) => return Object::Boolean(true),
+        _ => {}
+    }
+
+    let right = eval_expression(right, env);
+    if right.is_error() {
+        return right;
+    }
+    Object::Boolean(right.is_truthy())
+}
+
+/// Evaluates infix operators between two integers, arithmetic wraps around on overflow
+fn eval_integer_infix_expression(operator: &TokenKind, left: i64, right: i64) -> Object {
+    match operator {
+        TokenKind::Plus => Object::Integer(left.wrapping_add(right)),
+        TokenKind::Minus => Object::Integer(left.wrapping_sub(right)),
+        TokenKind::Star => Object::Integer(left.wrapping_mul(right)),
+        TokenKind::Slash | TokenKind::Percent if right == 0 => {
+            Object::Error("division by zero".to_string())
+        }
+        TokenKind::Slash => Object::Integer(left.wrapping_div(right)),
+        TokenKind::Percent => Object::Integer(left.wrapping_rem(right)),
+        TokenKind::BitAnd => Object::Integer(left & right),
+        TokenKind::BitOr => Object::Integer(left | right),
+        TokenKind::BitXor => Object::Integer(left ^ right),
+        // Shifting by the width of the integer or more is an error instead of silently wrapping
+        TokenKind::ShiftLeft | TokenKind::ShiftRight if !(0..64).contains(&right) => {
+            Object::Error(format!("shift amount out of range: {right}"))
+        }
+        TokenKind::ShiftLeft => Object::Integer(left << right),
+        // The sign is kept, -8 >> 1 is -4
+        TokenKind::ShiftRight => Object::Integer(left >> right),
+        TokenKind::LT => Object::Boolean(left < right),
+        TokenKind::GT => Object::Boolean(left > right),
+        TokenKind::LTEq => Object::Boolean(left <= right),
+        TokenKind::GTEq => Object::Boolean(left >= right),
+        TokenKind::Eq | TokenKind::NotEq => eval_equality(operator, left == right),
+        _ => Object::Error(format!("unknown operator: INTEGER {operator} INTEGER")),
+    }
+}
+
+/// Evaluates infix operators between two floats, they follow IEEE 754 so dividing by zero gives
+/// an infinity instead of an error
+fn eval_float_infix_expression(operator: &TokenKind, left: f64, right: f64) -> Object {
+    match operator {
+        TokenKind::Plus => Object::Float(left + right),
+        TokenKind::Minus => Object::Float(left - right),
+        TokenKind::Star => Object::Float(left * right),
+        TokenKind::Slash => Object::Float(left / right),
+        TokenKind::Percent => Object::Float(left % right),
+        TokenKind::LT => Object::Boolean(left < right),
+        TokenKind::GT => Object::Boolean(left > right),
+        TokenKind::LTEq => Object::Boolean(left <= right),
+        TokenKind::GTEq => Object::Boolean(left >= right),
+        TokenKind::Eq | TokenKind::NotEq => eval_equality(operator, left == right),
+        _ => Object::Error(format!("unknown operator: FLOAT {operator} FLOAT")),
+    }
+}
+
+/// Evaluates infix operators between two strings, `+` concatenates them
+fn eval_string_infix_expression(operator: &TokenKind, left: String, right: String) -> Object {
+    match operator {
+        TokenKind::Plus => Object::String(left + &right),
+        TokenKind::Eq | TokenKind::NotEq => eval_equality(operator, left == right),
+        _ => Object::Error(format!("unknown operator: STRING {operator} STRING")),
+    }
+}
+
+/// Turns the result of comparing two operands into the result of an `==` or `!=` operator
+fn eval_equality(operator: &TokenKind, equal: bool) -> Object {
+    match operator {
+        TokenKind::NotEq => Object::Boolean(!equal),
+        _ => Object::Boolean(equal),
+    }
+}
+
+/// Evaluates an if expression, if the condition is falsy and there is no alternative it yields
+/// null
+fn eval_if_expression(if_exp: &IfExp, env: &Env) -> Object {
+    let cond = eval_expression(&if_exp.cond, env);
+    if cond.is_error() {
+        return cond;
+    }
+
+    if cond.is_truthy() {
+        eval_block_statement(&if_exp.consequence, env)
+    } else if let Some(alternative) = &if_exp.alternative {
+        eval_block_statement(alternative, env)
+    } else {
+        Object::Null
+    }
+}
@@ -0,0 +1,760 @@
+#[cfg(test)]
+mod test {
+    use std::rc::Rc;
+
+    use crate::{
+        eval::{STACK_SIZE, eval_program},
+        lexer::Lexer,
+        object::{
+            Hash, HashKey, Object,
+            builtins::{Arity, BuiltinError, Builtins},
+            environment::Environment,
+        },
+        parser::Parser,
+    };
+
+    // Panics if the program cannot be parsed
+    fn test_eval(input: &str) -> Object {
+        let lexer = Lexer::new(input);
+        let mut parser = Parser::new(lexer);
+
+        match parser.parse_program() {
+            Ok(program) => eval_program(&program, &Environment::new().into_env()),
+            Err(errs) => {
+                for err in errs.iter() {
+                    eprintln!("Parsing error: {}", err)
+                }
+                panic!("Parsing exited with errors")
+            }
+        }
+    }
+
+    fn test_cases(cases: &[(&str, Object)]) {
+        for (input, expected) in cases.iter() {
+            assert_eq!(
+                &test_eval(input),
+                expected,
+                "Evaluated value does not match expected for: {input}"
+            )
+        }
+    }
+
+    #[test]
+    fn test_eval_integer_expression() {
+        let cases = [
+            ("5", Object::Integer(5)),
+            ("10", Object::Integer(10)),
+            ("-5", Object::Integer(-5)),
+            ("-10", Object::Integer(-10)),
+            ("5 + 5 + 5 + 5 - 10", Object::Integer(10)),
+            ("2 * 2 * 2 * 2 * 2", Object::Integer(32)),
+            ("-50 + 100 + -50", Object::Integer(0)),
+            ("5 * 2 + 10", Object::Integer(20)),
+            ("5 + 2 * 10", Object::Integer(25)),
+            ("20 + 2 * -10", Object::Integer(0)),
+            ("50 / 2 * 2 + 10", Object::Integer(60)),
+            ("2 * (5 + 10)", Object::Integer(30)),
+            ("3 * 3 * 3 + 10", Object::Integer(37)),
+            ("3 * (3 * 3) + 10", Object::Integer(37)),
+            ("(5 + 10 * 2 + 15 / 3) * 2 + -10", Object::Integer(50)),
+        ];
+
+        test_cases(&cases);
+    }
+
+    #[test]
+    fn test_eval_float_expression() {
+        let cases = [
+            ("2.75", Object::Float(2.75)),
+            ("-1e-9", Object::Float(-1e-9)),
+            ("0.5 + 0.25", Object::Float(0.75)),
+            ("1.5 * 2.0 - 1.0", Object::Float(2.0)),
+            ("1.0 / 0.0", Object::Float(f64::INFINITY)),
+            // Integers are promoted when mixed with floats
+            ("1 + 0.5", Object::Float(1.5)),
+            ("0.5 * 4", Object::Float(2.0)),
+            ("7 / 2.0", Object::Float(3.5)),
+            ("7 / 2", Object::Integer(3)),
+            ("1 < 1.5", Object::Boolean(true)),
+            ("2.5 > 3", Object::Boolean(false)),
+            ("1 == 1.0", Object::Boolean(true)),
+            ("0.1 + 0.2 != 0.3", Object::Boolean(true)),
+            ("int(-2.9)", Object::Integer(-2)),
+            ("float(3)", Object::Float(3.0)),
+            (r#"float("2.5")"#, Object::Float(2.5)),
+            ("type(1.0)", Object::String("FLOAT".to_string())),
+            ("str(2.0)", Object::String("2.0".to_string())),
+        ];
+
+        test_cases(&cases);
+
+        assert_eq!(
+            test_eval("1.5 + true"),
+            Object::Error("type mismatch: FLOAT + BOOLEAN".to_string())
+        );
+        assert_eq!(
+            test_eval("{1.5: 1}"),
+            Object::Error("unusable as hash key: FLOAT".to_string())
+        );
+    }
+
+    #[test]
+    fn test_eval_operators() {
+        let cases = [
+            ("7 % 3", Object::Integer(1)),
+            ("-7 % 3", Object::Integer(-1)),
+            ("7.5 % 2", Object::Float(1.5)),
+            ("1 <= 1", Object::Boolean(true)),
+            ("2 <= 1", Object::Boolean(false)),
+            ("1 >= 2", Object::Boolean(false)),
+            ("1.5 >= 1", Object::Boolean(true)),
+            ("6 & 3", Object::Integer(2)),
+            ("6 | 3", Object::Integer(7)),
+            ("6 ^ 3", Object::Integer(5)),
+            ("1 << 4", Object::Integer(16)),
+            ("-8 >> 1", Object::Integer(-4)),
+            ("~0", Object::Integer(-1)),
+            ("5 & 1 == 1", Object::Boolean(true)),
+            ("true && false", Object::Boolean(false)),
+            ("true || false", Object::Boolean(true)),
+            ("1 && \"a\"", Object::Boolean(true)),
+            ("null_value || 0", Object::Boolean(true)),
+            ("1 < 2 && 2 < 3", Object::Boolean(true)),
+            // The right side is not evaluated when the left one decides
+            ("false && undefined", Object::Boolean(false)),
+            ("true || 1 / 0", Object::Boolean(true)),
+        ];
+
+        for (input, expected) in cases.iter() {
+            let input = format!("let null_value = if (false) {{ 1 }}; {input}");
+            assert_eq!(
+                &test_eval(&input),
+                expected,
+                "Evaluated value does not match expected for: {input}"
+            )
+        }
+    }
+
+    #[test]
+    fn test_eval_boolean_expression() {
+        let cases = [
+            ("true", Object::Boolean(true)),
+            ("false", Object::Boolean(false)),
+            ("1 < 2", Object::Boolean(true)),
+            ("1 > 2", Object::Boolean(false)),
+            ("1 < 1", Object::Boolean(false)),
+            ("1 > 1", Object::Boolean(false)),
+            ("1 == 1", Object::Boolean(true)),
+            ("1 != 1", Object::Boolean(false)),
+            ("1 == 2", Object::Boolean(false)),
+            ("1 != 2", Object::Boolean(true)),
+            ("true == true", Object::Boolean(true)),
+            ("false == false", Object::Boolean(true)),
+            ("true == false", Object::Boolean(false)),
+            ("true != false", Object::Boolean(true)),
+            ("(1 < 2) == true", Object::Boolean(true)),
+            ("(1 > 2) == true", Object::Boolean(false)),
+        ];
+
+        test_cases(&cases);
+    }
+
+    #[test]
+    fn test_bang_operator() {
+        let cases = [
+            ("!true", Object::Boolean(false)),
+            ("!false", Object::Boolean(true)),
+            ("!5", Object::Boolean(false)),
+            ("!!true", Object::Boolean(true)),
+            ("!!false", Object::Boolean(false)),
+            ("!!5", Object::Boolean(true)),
+        ];
+
+        test_cases(&cases);
+    }
+
+    #[test]
+    fn test_if_else_expressions() {
+        let cases = [
+            ("if (true) { 10 }", Object::Integer(10)),
+            ("if (false) { 10 }", Object::Null),
+            ("if (1) { 10 }", Object::Integer(10)),
+            ("if (1 < 2) { 10 }", Object::Integer(10)),
+            ("if (1 > 2) { 10 }", Object::Null),
+            ("if (1 > 2) { 10 } else { 20 }", Object::Integer(20)),
+            ("if (1 < 2) { 10 } else { 20 }", Object::Integer(10)),
+        ];
+
+        test_cases(&cases);
+    }
+
+    #[test]
+    fn test_return_statements() {
+        // The statements after a return are never evaluated, even from nested blocks
+        let cases = [
+            ("return 10;", Object::Integer(10)),
+            ("return 10; 9;", Object::Integer(10)),
+            ("return 2 * 5; 9;", Object::Integer(10)),
+            ("9; return 2 * 5; 9;", Object::Integer(10)),
+            ("return; 9", Object::Null),
+            (
+                "if (10 > 1) { if (10 > 1) { return 10; } return 1; }",
+                Object::Integer(10),
+            ),
+            ("if (true) { if (true) { return; } 1 } 2", Object::Null),
+        ];
+
+        test_cases(&cases);
+    }
+
+    #[test]
+    fn test_let_statements() {
+        let cases = [
+            ("let a = 5; a;", Object::Integer(5)),
+            ("let a = 5 * 5; a;", Object::Integer(25)),
+            ("let a = 5; let b = a; b;", Object::Integer(5)),
+            (
+                "let a = 5; let b = a; let c = a + b + 5; c;",
+                Object::Integer(15),
+            ),
+        ];
+
+        test_cases(&cases);
+    }
+
+    #[test]
+    fn test_error_handling() {
+        let cases = [
+            ("5 + true;", "type mismatch: INTEGER + BOOLEAN"),
+            ("5 + true; 5;", "type mismatch: INTEGER + BOOLEAN"),
+            ("-true", "unknown operator: -BOOLEAN"),
+            ("true + false;", "unknown operator: BOOLEAN + BOOLEAN"),
+            ("5; true + false; 5", "unknown operator: BOOLEAN + BOOLEAN"),
+            (
+                "if (10 > 1) { true + false; }",
+                "unknown operator: BOOLEAN + BOOLEAN",
+            ),
+            (
+                "if (10 > 1) { if (10 > 1) { true + false; } return; }",
+                "unknown operator: BOOLEAN + BOOLEAN",
+            ),
+            ("true > false", "unknown operator: BOOLEAN > BOOLEAN"),
+            ("-(1 < 2) + 1", "unknown operator: -BOOLEAN"),
+            ("10 / 0", "division by zero"),
+            ("10 % 0", "division by zero"),
+            ("1 << 64", "shift amount out of range: 64"),
+            ("1 >> -1", "shift amount out of range: -1"),
+            ("~true", "unknown operator: ~BOOLEAN"),
+            ("1.5 & 1", "unknown operator: FLOAT & FLOAT"),
+            ("true && 1 + true", "type mismatch: INTEGER + BOOLEAN"),
+            ("foobar", "identifier not found: foobar"),
+            (r#""Hello" - "World""#, "unknown operator: STRING - STRING"),
+            (r#""a" + 1"#, "type mismatch: STRING + INTEGER"),
+            ("1[0]", "index operator not supported: INTEGER"),
+            ("[1][true]", "array index must be INTEGER, got BOOLEAN"),
+            ("[1][0:true]", "slice bounds must be INTEGER, got BOOLEAN"),
+            ("true[0:1]", "slice operator not supported: BOOLEAN"),
+            // Each operand of a slice is checked before the next one is evaluated
+            ("true[1 / 0:]", "slice operator not supported: BOOLEAN"),
+            (
+                "[1][true:1 / 0]",
+                "slice bounds must be INTEGER, got BOOLEAN",
+            ),
+            (
+                r#"{"name": "Monkey"}[fn(x) { x }];"#,
+                "unusable as hash key: FUNCTION",
+            ),
+            ("{[1]: 2}", "unusable as hash key: ARRAY"),
+        ];
+
+        for (input, message) in cases.iter() {
+            assert_eq!(
+                test_eval(input),
+                Object::Error(message.to_string()),
+                "Unexpected error for: {input}"
+            )
+        }
+    }
+
+    #[test]
+    fn test_function_object() {
+        match test_eval("fn(x) { x + 2; };") {
+            Object::Function(function) => {
+                assert_eq!(function.parameters.len(), 1);
+                assert_eq!(function.parameters[0].name, "x");
+                assert_eq!(function.body.to_string(), "(x + 2)");
+            }
+            obj => panic!("Expected a function got: {obj}"),
+        }
+    }
+
+    #[test]
+    fn test_function_application() {
+        let cases = [
+            (
+                "let identity = fn(x) { x; }; identity(5);",
+                Object::Integer(5),
+            ),
+            (
+                "let identity = fn(x) { return x; }; identity(5);",
+                Object::Integer(5),
+            ),
+            (
+                "let double = fn(x) { x * 2; }; double(5);",
+                Object::Integer(10),
+            ),
+            (
+                "let add = fn(x, y) { x + y; }; add(5, 5);",
+                Object::Integer(10),
+            ),
+            (
+                "let add = fn(x, y) { x + y; }; add(5 + 5, add(5, 5));",
+                Object::Integer(20),
+            ),
+            ("fn(x) { x; }(5)", Object::Integer(5)),
+            (
+                "let f = fn(x) { return x; 10 }; f(1) + f(2);",
+                Object::Integer(3),
+            ),
+            (
+                "let fact = fn(n) { if (n < 2) { return 1; } n * fact(n - 1) }; fact(10);",
+                Object::Integer(3628800),
+            ),
+        ];
+
+        test_cases(&cases);
+    }
+
+    #[test]
+    fn test_closures() {
+        let cases = [
+            (
+                "let newAdder = fn(x) { fn(y) { x + y }; }; let addTwo = newAdder(2); addTwo(2);",
+                Object::Integer(4),
+            ),
+            (
+                "let x = 10; let f = fn() { let x = 1; x }; f() + x;",
+                Object::Integer(11),
+            ),
+            (
+                "let curry = fn(a) { fn(b) { fn(c) { a + b + c } } }; curry(1)(2)(3)",
+                Object::Integer(6),
+            ),
+        ];
+
+        test_cases(&cases);
+    }
+
+    #[test]
+    fn test_function_errors() {
+        let cases = [
+            ("5(1)", "not a function: INTEGER"),
+            (
+                "fn(x) { x }(1, 2)",
+                "wrong number of arguments: want=1, got=2",
+            ),
+            ("let f = fn() { y }; f()", "identifier not found: y"),
+            (
+                "let f = fn(x) { x }; f(true + 1)",
+                "type mismatch: BOOLEAN + INTEGER",
+            ),
+        ];
+
+        for (input, message) in cases.iter() {
+            assert_eq!(
+                test_eval(input),
+                Object::Error(message.to_string()),
+                "Unexpected error for: {input}"
+            )
+        }
+    }
+
+    #[test]
+    fn test_stack_overflow() {
+        // Deep enough to need more than the stack of the test threads
+        let run = |input: String| {
+            std::thread::Builder::new()
+                .stack_size(STACK_SIZE)
+                .spawn(move || test_eval(&input).to_string())
+                .unwrap()
+                .join()
+                .unwrap()
+        };
+
+        let input = "let f = fn(n) { if (n == 0) { 0 } else { 1 + f(n - 1) } };";
+        assert_eq!(run(input.to_string() + " f(1022)"), "1022");
+        assert_eq!(run(input.to_string() + " f(1023)"), "ERROR: stack overflow");
+        assert_eq!(
+            run("let f = fn(x) { f(x + 1) }; f(0)".to_string()),
+            "ERROR: stack overflow"
+        );
+    }
+
+    #[test]
+    fn test_strings() {
+        let cases = [
+            (
+                r#""Hello World!""#,
+                Object::String("Hello World!".to_string()),
+            ),
+            (
+                r#""Hello" + " " + "World!""#,
+                Object::String("Hello World!".to_string()),
+            ),
+            (
+                r#"let greet = fn(name) { "Hi " + name }; greet("\u{1F600}")"#,
+                Object::String("Hi \u{1F600}".to_string()),
+            ),
+            (r#""a" == "a""#, Object::Boolean(true)),
+            (r#""a" != "a""#, Object::Boolean(false)),
+        ];
+
+        test_cases(&cases);
+    }
+
+    fn int_array(values: &[i64]) -> Object {
+        Object::Array(Rc::new(
+            values.iter().map(|v| Object::Integer(*v)).collect(),
+        ))
+    }
+
+    #[test]
+    fn test_array_literals() {
+        let cases = [
+            ("[1, 2 * 2, 3 + 3]", int_array(&[1, 4, 6])),
+            ("[]", int_array(&[])),
+        ];
+
+        test_cases(&cases);
+    }
+
+    #[test]
+    fn test_array_index_expressions() {
+        let cases = [
+            ("[1, 2, 3][0]", Object::Integer(1)),
+            ("[1, 2, 3][1]", Object::Integer(2)),
+            ("[1, 2, 3][2]", Object::Integer(3)),
+            ("let i = 0; [1][i];", Object::Integer(1)),
+            ("[1, 2, 3][1 + 1];", Object::Integer(3)),
+            ("let myArray = [1, 2, 3]; myArray[2];", Object::Integer(3)),
+            (
+                "let myArray = [1, 2, 3]; myArray[0] + myArray[1] + myArray[2];",
+                Object::Integer(6),
+            ),
+            (
+                "let myArray = [1, 2, 3]; let i = myArray[0]; myArray[i]",
+                Object::Integer(2),
+            ),
+            ("[1, 2, 3][3]", Object::Null),
+            ("[1, 2, 3][-1]", Object::Integer(3)),
+            ("[1, 2, 3][-3]", Object::Integer(1)),
+            ("[1, 2, 3][-4]", Object::Null),
+        ];
+
+        test_cases(&cases);
+    }
+
+    #[test]
+    fn test_array_slices() {
+        let cases = [
+            ("[1, 2, 3, 4][1:3]", int_array(&[2, 3])),
+            ("[1, 2, 3, 4][:2]", int_array(&[1, 2])),
+            ("[1, 2, 3, 4][2:]", int_array(&[3, 4])),
+            ("[1, 2, 3, 4][:]", int_array(&[1, 2, 3, 4])),
+            ("[1, 2, 3, 4][-2:]", int_array(&[3, 4])),
+            ("[1, 2, 3, 4][:-1]", int_array(&[1, 2, 3])),
+            ("[1, 2, 3, 4][-10:10]", int_array(&[1, 2, 3, 4])),
+            ("[1, 2, 3, 4][3:1]", int_array(&[])),
+        ];
+
+        test_cases(&cases);
+    }
+
+    #[test]
+    fn test_hash_literals() {
+        let input = r#"
+            let two = "two";
+            {
+                "one": 10 - 9,
+                two: 1 + 1,
+                "thr" + "ee": 6 / 2,
+                4: 4,
+                true: 5,
+                false: 6
+            }
+        "#;
+
+        let mut expected = Hash::new();
+        expected.insert(HashKey::String("one".to_string()), Object::Integer(1));
+        expected.insert(HashKey::String("two".to_string()), Object::Integer(2));
+        expected.insert(HashKey::String("three".to_string()), Object::Integer(3));
+        expected.insert(HashKey::Integer(4), Object::Integer(4));
+        expected.insert(HashKey::Boolean(true), Object::Integer(5));
+        expected.insert(HashKey::Boolean(false), Object::Integer(6));
+
+        assert_eq!(test_eval(input), Object::Hash(Rc::new(expected)));
+    }
+
+    #[test]
+    fn test_hash_index_expressions() {
+        let cases = [
+            (r#"{"foo": 5}["foo"]"#, Object::Integer(5)),
+            (r#"{"foo": 5}["bar"]"#, Object::Null),
+            (r#"let key = "foo"; {"foo": 5}[key]"#, Object::Integer(5)),
+            (r#"{}["foo"]"#, Object::Null),
+            ("{5: 5}[5]", Object::Integer(5)),
+            ("{true: 5}[true]", Object::Integer(5)),
+            ("{false: 5}[false]", Object::Integer(5)),
+            ("{1: 1, 1: 2}[1]", Object::Integer(2)),
+        ];
+
+        test_cases(&cases);
+    }
+
+    #[test]
+    fn test_builtin_functions() {
+        let cases = [
+            (r#"len("")"#, Object::Integer(0)),
+            (r#"len("four")"#, Object::Integer(4)),
+            (r#"len("hello world")"#, Object::Integer(11)),
+            ("len([1, 2, 3])", Object::Integer(3)),
+            ("len({1: 2})", Object::Integer(1)),
+            ("first([1, 2, 3])", Object::Integer(1)),
+            ("first([])", Object::Null),
+            ("last([1, 2, 3])", Object::Integer(3)),
+            ("last([])", Object::Null),
+            ("rest([1, 2, 3])", int_array(&[2, 3])),
+            ("rest([])", Object::Null),
+            ("push([], 1)", int_array(&[1])),
+            ("let a = [1]; push(a, 2); a", int_array(&[1])),
+            ("puts(1, 2)", Object::Null),
+            ("type(1)", Object::String("INTEGER".to_string())),
+            ("type(len)", Object::String("BUILTIN".to_string())),
+            ("str([1, true])", Object::String("[1, true]".to_string())),
+            (r#"int(" 42")"#, Object::Integer(42)),
+            ("int(true)", Object::Integer(1)),
+            // Bindings shadow the builtins
+            ("let len = fn(x) { 0 }; len([1])", Object::Integer(0)),
+        ];
+
+        test_cases(&cases);
+    }
+
+    #[test]
+    fn test_builtin_errors() {
+        let cases = [
+            (
+                "len(1)",
+                "argument 1 to len must be STRING, ARRAY or HASH, got INTEGER",
+            ),
+            (
+                r#"len("one", "two")"#,
+                "wrong number of arguments to len: want=1, got=2",
+            ),
+            ("first(1)", "argument 1 to first must be ARRAY, got INTEGER"),
+            (
+                "push(1, 1)",
+                "argument 1 to push must be ARRAY, got INTEGER",
+            ),
+            (r#"int("abc")"#, r#"could not convert "abc" to INTEGER"#),
+        ];
+
+        for (input, message) in cases.iter() {
+            assert_eq!(
+                test_eval(input),
+                Object::Error(message.to_string()),
+                "Unexpected error for: {input}"
+            )
+        }
+    }
+
+    #[test]
+    fn test_loops() {
+        let cases = [
+            ("while (false) { 1 }", Object::Null),
+            ("while (true) { break; }", Object::Null),
+            ("for (x in []) { x }", Object::Null),
+            (
+                "let f = fn() { for (i in 0..10) { if (i == 3) { return i; } } }; f()",
+                Object::Integer(3),
+            ),
+            (
+                "let f = fn() { for (i in 0..5) { if (i < 4) { continue; } return i; } }; f()",
+                Object::Integer(4),
+            ),
+            (
+                "let f = fn() { while (true) { return 1; } }; f()",
+                Object::Integer(1),
+            ),
+            (
+                "let f = fn() { for (x in [5, 6]) { break; } return 0; }; f()",
+                Object::Integer(0),
+            ),
+            // Ranges are not materialized
+            (
+                "let f = fn() { for (i in 0..1000000000000) { if (i == 2) { return i; } } }; f()",
+                Object::Integer(2),
+            ),
+            (
+                r#"let f = fn() { for (c in "ñb") { return c; } }; f()"#,
+                Object::String("ñ".to_string()),
+            ),
+            (
+                r#"let f = fn() { for (k in {"a": 1, "b": 2}) { if (k == "b") { return k; } } }; f()"#,
+                Object::String("b".to_string()),
+            ),
+            // Each iteration has its own binding
+            (
+                "let fs = fn() { for (i in [1, 2]) { return fn() { i }; } }; fs()()",
+                Object::Integer(1),
+            ),
+            // Only the innermost loop is stopped
+            (
+                "let f = fn() { for (i in 0..3) { for (j in 0..3) { break; } if (i == 2) { return i; } } }; f()",
+                Object::Integer(2),
+            ),
+            ("0..3", Object::Range(0, 3)),
+        ];
+
+        test_cases(&cases);
+
+        let errors = [
+            ("break;", "break outside of a loop"),
+            (
+                "let f = fn() { continue; }; f()",
+                "continue outside of a loop",
+            ),
+            (
+                "for (i in 0..3) { let f = fn() { break; }; f() }",
+                "break outside of a loop",
+            ),
+            ("for (x in 5) { }", "iteration not supported: INTEGER"),
+            ("0..true", "range bounds must be INTEGER, got BOOLEAN"),
+            ("true..(1 / 0)", "range bounds must be INTEGER, got BOOLEAN"),
+            ("while (1 + true) { }", "type mismatch: INTEGER + BOOLEAN"),
+            (
+                "for (x in [1]) { x + true }",
+                "type mismatch: INTEGER + BOOLEAN",
+            ),
+        ];
+        for (input, message) in errors {
+            assert_eq!(
+                test_eval(input),
+                Object::Error(message.to_string()),
+                "Unexpected error for: {input}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_assignments() {
+        let cases = [
+            ("let x = 1; x = 5; x", Object::Integer(5)),
+            ("let x = 1; x = 5", Object::Integer(5)),
+            ("let x = 1; x += 2; x", Object::Integer(3)),
+            (
+                "let x = 10; x -= 2; x *= 3; x /= 4; x %= 4; x",
+                Object::Integer(2),
+            ),
+            ("let x = 1.5; x += 1; x", Object::Float(2.5)),
+            (
+                r#"let s = "a"; s += "b"; s"#,
+                Object::String("ab".to_string()),
+            ),
+            ("let a = 1; let b = 2; a = b = 3; a + b", Object::Integer(6)),
+            ("let a = [1, 2]; a[0] = 5; a", int_array(&[5, 2])),
+            ("let a = [1, 2]; a[-1] += 5; a", int_array(&[1, 7])),
+            ("let m = [[1], [2]]; m[1][0] = 9; m[1]", int_array(&[9])),
+            // Collections are values, copies are not changed
+            ("let a = [1]; let b = a; b[0] = 2; a", int_array(&[1])),
+            (
+                r#"let h = {}; h["k"] = 1; h["k"] += 1; h["k"]"#,
+                Object::Integer(2),
+            ),
+            (
+                r#"let h = {"a": [0]}; h["a"][0] = 3; h["a"]"#,
+                int_array(&[3]),
+            ),
+            // The nearest enclosing binding is changed
+            (
+                "let x = 1; let f = fn() { x = 2 }; f(); x",
+                Object::Integer(2),
+            ),
+            (
+                "let x = 1; let f = fn() { let x = 5; x = 6 }; f(); x",
+                Object::Integer(1),
+            ),
+            (
+                "let counter = fn() { let c = 0; fn() { c += 1; c } }; let next = counter(); next(); next()",
+                Object::Integer(2),
+            ),
+            (
+                "let sum = 0; for (i in 1..5) { sum += i }; sum",
+                Object::Integer(10),
+            ),
+            ("let i = 0; while (i < 5) { i += 1 }; i", Object::Integer(5)),
+            // The indexes of the target are evaluated once, before the value
+            (
+                "let n = 0; let f = fn() { n += 1; n }; let a = [0, 0, 0, 0]; a[f()] += 10; [a, n]",
+                Object::Array(Rc::new(vec![int_array(&[0, 10, 0, 0]), Object::Integer(1)])),
+            ),
+            (
+                "let log = []; let f = fn(x) { log = push(log, x); x }; let m = [[0, 0], [0, 0]]; m[f(1)][f(0)] = f(5); [m, log]",
+                Object::Array(Rc::new(vec![
+                    Object::Array(Rc::new(vec![int_array(&[0, 0]), int_array(&[5, 0])])),
+                    int_array(&[1, 0, 5]),
+                ])),
+            ),
+            (
+                "let i = 0; while (true) { i += 1; if (i == 3) { break; } }; i",
+                Object::Integer(3),
+            ),
+        ];
+
+        test_cases(&cases);
+
+        let errors = [
+            ("x = 5", "assignment to undeclared variable: x"),
+            ("x += 1", "assignment to undeclared variable: x"),
+            ("len = 1", "assignment to undeclared variable: len"),
+            (
+                "let f = fn() { let y = 1; }; f(); y = 2",
+                "assignment to undeclared variable: y",
+            ),
+            ("let a = [1]; a[1] = 2", "index out of range: 1"),
+            (
+                "let a = [1]; a[true] = 2",
+                "array index must be INTEGER, got BOOLEAN",
+            ),
+            ("let h = {}; h[[]] = 2", "unusable as hash key: ARRAY"),
+            (
+                "let s = \"a\"; s[0] = 2",
+                "index assignment not supported: STRING",
+            ),
+            ("let x = 1; x += true", "type mismatch: INTEGER + BOOLEAN"),
+            ("let x = 1; x = y", "identifier not found: y"),
+        ];
+        for (input, message) in errors {
+            assert_eq!(
+                test_eval(input),
+                Object::Error(message.to_string()),
+                "Unexpected error for: {input}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_custom_builtins() {
+        let mut builtins = Builtins::default();
+        builtins.register("double", Arity::Exact(1), |args| match &args[0] {
+            Object::Integer(value) => Ok(Object::Integer(value * 2)),
+            _ => Err(BuiltinError::wrong_type("double", args, 0, "INTEGER")),
+        });
+        let env = Environment::with_builtins(builtins).into_env();
+
+        let program = Parser::new(Lexer::new("double(len([1, 2]))"))
+            .parse_program()
+            .unwrap();
+        assert_eq!(eval_program(&program, &env), Object::Integer(4));
+    }
+}
@@ -1,5 +1,7 @@
+pub mod error;
 pub mod token;
 
+use error::LexError;
 use token::{Token, TokenKind};
 
 pub struct Lexer<'s> {
@@ -7,6 +9,8 @@ pub struct Lexer<'s> {
     pos: usize,
     next_pos: usize,
     ch: char,
+    /// Whether comments are returned as tokens instead of being skipped
+    keep_comments: bool,
 }
 
 impl<'s> Lexer<'s> {
@@ -16,39 +20,69 @@ impl<'s> Lexer<'s> {
             pos: 0,
             next_pos: 0,
             ch: 0 as char,
+            keep_comments: false,
         };
 
         l.read_char();
         l
     }
 
-    /// Read the next character and advances both current and next position
+    /// Returns the comments as trivia tokens, tools like a formatter need them to round-trip the
+    /// source while the parser does not want them
+    pub fn with_comments(mut self) -> Self {
+        self.keep_comments = true;
+        self
+    }
+
+    /// Read the next character and advances both current and next position, positions are byte
+    /// offsets so they always fall on char boundaries
     /// If there's nothing more to advance, returns ascii 0 which is null
     fn read_char(&mut self) {
-        if self.next_pos >= self.source.len() {
-            self.ch = 0 as char;
-        } else {
-            self.ch = self.source.as_bytes()[self.next_pos] as char;
-        }
-
-        self.pos = self.next_pos;
-        self.next_pos += 1;
+        // Never go past the end, the eof token must point right after the last char
+        self.pos = self.next_pos.min(self.source.len());
+        self.ch = self.char_at(self.pos);
+        self.next_pos = self.pos + self.ch.len_utf8();
     }
 
     /// Read the next character without advancing the current position
     /// If there's nothing more to advance, returns ascii 0 which is null
     /// This allows to see which is the immediate next character
     fn peek_char(&self) -> char {
-        if self.next_pos >= self.source.len() {
-            return 0 as char;
+        self.char_at(self.next_pos)
+    }
+
+    /// Reads the next char only if it's the expected one, used for operators of two chars
+    fn followed_by(&mut self, expected: char) -> bool {
+        if self.peek_char() == expected {
+            self.read_char();
+            true
+        } else {
+            false
         }
+    }
+
+    /// Returns the char starting at the byte offset, or ascii 0 past the end of the source
+    /// The source may contain ascii 0 too, use the positions to know if the end is reached
+    fn char_at(&self, offset: usize) -> char {
+        self.source
+            .get(offset..)
+            .and_then(|rest| rest.chars().next())
+            .unwrap_or('\u{0}')
+    }
+
+    /// Whether the current position is past the last char
+    fn at_end(&self) -> bool {
+        self.pos >= self.source.len()
+    }
 
-        self.source.as_bytes()[self.next_pos] as char
+    /// Whether the current char is the last one
+    fn peek_at_end(&self) -> bool {
+        self.next_pos >= self.source.len()
     }
 
-    /// Reads until it doesn't find an asci whitespace
+    /// Reads until it doesn't find a whitespace, unicode ones included
     fn skip_withespace(&mut self) {
-        while self.ch.is_ascii_whitespace() {
+        while self.ch.is_whitespace() {
             self.read_char();
         }
     }
@@ -59,44 +93,275 @@ impl<'s> Lexer<'s> {
         // Read the next char
         let start_ident = self.pos;
 
-        // Read as long as the peeked next character stops being an ascii letter
-        // We still verify if the current is a letter, this helps if the caller sent a wrong
+        // Read as long as the peeked next character can continue the identifier
+        // We still verify if the current is valid, this helps if the caller sent a wrong
         // char.
-        while is_letter(self.ch) && is_letter(self.peek_char()) {
+        while is_ident_continue(self.ch) && is_ident_continue(self.peek_char()) {
             self.read_char();
         }
 
         // Get the token kind, can either bey a keyword or a custom identifier
-        let kind = token::lookup_identifier(&self.source[start_ident..=self.pos]);
+        let kind = token::lookup_identifier(&self.source[start_ident..self.next_pos]);
 
-        // Return
-        (kind, start_ident, self.pos)
+        // Return, the end is exclusive
+        (kind, start_ident, self.next_pos)
     }
 
-    /// Parse integer returns the kind which will be an integer with the beginning and the end of the token
-    fn parse_integer(&mut self) -> (TokenKind, usize, usize) {
+    /// Parse number returns the kind which will be an integer or a float with the beginning and the
+    /// end of the token
+    /// Integers can be written in hex, octal or binary with the 0x, 0o and 0b prefixes and their
+    /// digits can be separated with underscores, like 1_000_000
+    /// Floats are decimal numbers with a fraction, an exponent or both, like 3.14 or 1e-9
+    fn parse_number(&mut self) -> (TokenKind, usize, usize) {
         let start_ident = self.pos;
 
-        // Read as long as the peeked next character stops being a digit
-        // We still verify if the current is a letter, this helps if the caller sent a wrong
-        // char.
-        while self.ch.is_ascii_digit() && self.peek_char().is_ascii_digit() {
+        let radix = match (self.ch, self.peek_char()) {
+            ('0', 'x') => 16,
+            ('0', 'o') => 8,
+            ('0', 'b') => 2,
+            _ => 10,
+        };
+        // Skip the prefix, the digits start right after it
+        let digits_start = if radix == 10 {
+            self.pos
+        } else {
+            self.read_char();
+            self.next_pos
+        };
+
+        if radix == 10 && self.read_float_part() {
+            return self.parse_float(start_ident);
+        }
+
+        // Read as long as the peeked next character can be part of a number, letters included so
+        // literals like 0b12 or 12ab are reported instead of being split in several tokens
+        self.skip_number_suffix();
+
+        let end = self.next_pos;
+        let digits: String = self.source[digits_start..end]
+            .chars()
+            .filter(|c| *c != '_')
+            .collect();
+
+        if digits.is_empty() {
+            return (
+                TokenKind::Illegal(LexError::MissingDigits),
+                start_ident,
+                end,
+            );
+        }
+        if let Some(digit) = digits.chars().find(|c| !c.is_digit(radix)) {
+            let kind = TokenKind::Illegal(LexError::InvalidDigit { digit, radix });
+            return (kind, start_ident, end);
+        }
+
+        // Only valid digits are left so the parse can only fail if the number does not fit
+        let kind = match i64::from_str_radix(&digits, radix) {
+            Ok(int) => TokenKind::Int(int),
+            Err(_) => TokenKind::Illegal(LexError::IntegerOverflow),
+        };
+        (kind, start_ident, end)
+    }
+
+    /// Reads the digits, fraction and exponent of a decimal number, returns true if it had a
+    /// fraction or an exponent and is therefore a float
+    fn read_float_part(&mut self) -> bool {
+        self.read_decimal_digits();
+        let mut is_float = false;
+
+        // The dot must be followed by a digit, otherwise it's not part of the number
+        if self.peek_char() == '.' && self.char_at(self.next_pos + 1).is_ascii_digit() {
+            self.read_char();
+            self.read_decimal_digits();
+            is_float = true;
+        }
+
+        if matches!(self.peek_char(), 'e' | 'E') {
+            let sign = matches!(self.char_at(self.next_pos + 1), '+' | '-');
+            let first_digit = self.char_at(self.next_pos + 1 + sign as usize);
+
+            // Without digits the e is left to be reported as an invalid digit
+            if first_digit.is_ascii_digit() {
+                self.read_char();
+                if sign {
+                    self.read_char();
+                }
+                self.read_decimal_digits();
+                is_float = true;
+            }
+        }
+
+        is_float
+    }
+
+    fn read_decimal_digits(&mut self) {
+        while self.peek_char().is_ascii_digit() || self.peek_char() == '_' {
+            self.read_char();
+        }
+    }
+
+    /// Reads any letter or digit stuck to the end of a number
+    fn skip_number_suffix(&mut self) {
+        while self.peek_char().is_ascii_alphanumeric() || self.peek_char() == '_' {
             self.read_char();
         }
+    }
 
-        // Parse the integer
-        let int: i64 = self.source[start_ident..=self.pos]
-            .parse()
-            // This is safe since we know there are only digits in the range
-            .unwrap_or_default();
+    /// Builds the float token once its text has been read, the current char must be the last
+    /// one of the float
+    fn parse_float(&mut self, start: usize) -> (TokenKind, usize, usize) {
+        let float_end = self.next_pos;
+        self.skip_number_suffix();
+
+        let end = self.next_pos;
+        if let Some(digit) = self.source[float_end..end].chars().next() {
+            let kind = TokenKind::Illegal(LexError::InvalidDigit { digit, radix: 10 });
+            return (kind, start, end);
+        }
 
-        // Now we build the integer kind
-        let kind = TokenKind::Int(int);
-        (kind, start_ident, self.pos)
+        let text: String = self.source[start..end]
+            .chars()
+            .filter(|c| *c != '_')
+            .collect();
+        let value: f64 = text.parse().expect("the text was scanned as a valid float");
+
+        let kind = if value.is_finite() {
+            TokenKind::Float(value)
+        } else {
+            TokenKind::Illegal(LexError::FloatOverflow)
+        };
+        (kind, start, end)
     }
 
-    // Tokenizes the next char
+    /// Parse string returns the kind which will be a string with the beginning and the end of the
+    /// token, the current char must be the opening quote and it will end on the closing one
+    fn parse_string(&mut self) -> (TokenKind, usize, usize) {
+        let start_ident = self.pos;
+        let mut value = String::new();
+
+        loop {
+            self.read_char();
+
+            match self.ch {
+                _ if self.at_end() => {
+                    let kind = TokenKind::Illegal(LexError::UnterminatedString);
+                    return (kind, start_ident, self.source.len());
+                }
+                '"' => break,
+                '\\' => {
+                    self.read_char();
+                    match self.read_escape() {
+                        Some(c) => value.push(c),
+                        None => {
+                            // Skip the rest of the string so it is not lexed as code
+                            while self.peek_char() != '"' && !self.peek_at_end() {
+                                self.read_char();
+                            }
+                            self.read_char();
+                            let end = self.next_pos.min(self.source.len());
+                            return (
+                                TokenKind::Illegal(LexError::InvalidEscape),
+                                start_ident,
+                                end,
+                            );
+                        }
+                    }
+                }
+                c => value.push(c),
+            }
+        }
+
+        (TokenKind::String(value), start_ident, self.next_pos)
+    }
+
+    /// Reads the escape sequence, the current char must be the one following the backslash and
+    /// it will end on the last char of the sequence
+    fn read_escape(&mut self) -> Option<char> {
+        match self.ch {
+            'n' => Some('\n'),
+            't' => Some('\t'),
+            'r' => Some('\r'),
+            '0' => Some('\0'),
+            '"' => Some('"'),
+            '\\' => Some('\\'),
+            'u' => {
+                // Unicode escapes are written as \u{XXXX} with up to six hex digits
+                if self.peek_char() != '{' {
+                    return None;
+                }
+                self.read_char();
+
+                let mut code = String::new();
+                while self.peek_char().is_ascii_hexdigit() && code.len() < 6 {
+                    self.read_char();
+                    code.push(self.ch);
+                }
+
+                if self.peek_char() != '}' || code.is_empty() {
+                    return None;
+                }
+                self.read_char();
+
+                char::from_u32(u32::from_str_radix(&code, 16).ok()?)
+            }
+            _ => None,
+        }
+    }
+
+    /// Parse comment returns a comment kind with the whole text of the comment, the current char
+    /// must be the first slash and it will end on the last char of the comment
+    /// Block comments can be nested, each /* must be closed by its own */
+    fn parse_comment(&mut self) -> (TokenKind, usize, usize) {
+        let start_ident = self.pos;
+
+        if self.peek_char() == '/' {
+            // Line comments go up to the line break, which is left for the whitespaces
+            while self.peek_char() != '\n' && !self.peek_at_end() {
+                self.read_char();
+            }
+        } else {
+            // Skip the opening star so it is not taken as part of a closing one
+            self.read_char();
+            let mut depth = 1;
+
+            while depth > 0 {
+                self.read_char();
+
+                match (self.ch, self.peek_char()) {
+                    _ if self.at_end() => {
+                        let kind = TokenKind::Illegal(LexError::UnterminatedComment);
+                        return (kind, start_ident, self.source.len());
+                    }
+                    ('*', '/') => {
+                        self.read_char();
+                        depth -= 1;
+                    }
+                    ('/', '*') => {
+                        self.read_char();
+                        depth += 1;
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        let text = self.source[start_ident..self.next_pos].to_string();
+        (TokenKind::Comment(text), start_ident, self.next_pos)
+    }
+
+    // Tokenizes the next char, the span of the token goes from its first byte to the byte
+    // following its last one
+    // Comments are skipped unless the lexer was asked to keep them
     pub fn next_token(&mut self) -> Token {
+        loop {
+            let token = self.scan_token();
+            if self.keep_comments || !matches!(token.kind, TokenKind::Comment(_)) {
+                return token;
+            }
+        }
+    }
+
+    fn scan_token(&mut self) -> Token {
         // Start by skipping whitespaces
         self.skip_withespace();
 
@@ -109,6 +374,8 @@ impl<'s> Lexer<'s> {
             ')' => Token::new(TokenKind::RightParen, start, end),
             '{' => Token::new(TokenKind::LeftBrace, start, end),
             '}' => Token::new(TokenKind::RightBrace, start, end),
+            '[' => Token::new(TokenKind::LeftBracket, start, end),
+            ']' => Token::new(TokenKind::RightBracket, start, end),
             '=' => {
                 // Check if its an Eq token
                 if self.peek_char() == '=' {
@@ -119,10 +386,17 @@ impl<'s> Lexer<'s> {
                     Token::new(TokenKind::Assign, start, end)
                 }
             }
-            '+' => Token::new(TokenKind::Plus, start, end),
+            '+' => {
+                if self.followed_by('=') {
+                    Token::new(TokenKind::PlusAssign, start, self.next_pos)
+                } else {
+                    Token::new(TokenKind::Plus, start, end)
+                }
+            }
             ';' => Token::new(TokenKind::SemiColon, start, end),
+            ':' => Token::new(TokenKind::Colon, start, end),
             ',' => Token::new(TokenKind::Comma, start, end),
-            '\u{0}' => Token::new(TokenKind::Eof, start, end),
+            _ if self.at_end() => Token::new(TokenKind::Eof, start, start),
             '!' => {
                 // Check if it's a NotEq token
                 if self.peek_char() == '=' {
@@ -134,28 +408,100 @@ impl<'s> Lexer<'s> {
                     Token::new(TokenKind::Bang, start, end)
                 }
             }
-            '/' => Token::new(TokenKind::Slash, start, end),
-            '<' => Token::new(TokenKind::LT, start, end),
-            '>' => Token::new(TokenKind::GT, start, end),
-            '-' => Token::new(TokenKind::Minus, start, end),
-            '*' => Token::new(TokenKind::Star, start, end),
+            '/' if matches!(self.peek_char(), '/' | '*') => {
+                let (kind, start, end) = self.parse_comment();
+                Token::new(kind, start, end)
+            }
+            '/' => {
+                if self.followed_by('=') {
+                    Token::new(TokenKind::SlashAssign, start, self.next_pos)
+                } else {
+                    Token::new(TokenKind::Slash, start, end)
+                }
+            }
+            '<' => {
+                if self.followed_by('=') {
+                    Token::new(TokenKind::LTEq, start, self.next_pos)
+                } else if self.followed_by('<') {
+                    Token::new(TokenKind::ShiftLeft, start, self.next_pos)
+                } else {
+                    Token::new(TokenKind::LT, start, end)
+                }
+            }
+            '>' => {
+                if self.followed_by('=') {
+                    Token::new(TokenKind::GTEq, start, self.next_pos)
+                } else if self.followed_by('>') {
+                    Token::new(TokenKind::ShiftRight, start, self.next_pos)
+                } else {
+                    Token::new(TokenKind::GT, start, end)
+                }
+            }
+            '&' => {
+                if self.followed_by('&') {
+                    Token::new(TokenKind::And, start, self.next_pos)
+                } else {
+                    Token::new(TokenKind::BitAnd, start, end)
+                }
+            }
+            '|' => {
+                if self.followed_by('|') {
+                    Token::new(TokenKind::Or, start, self.next_pos)
+                } else {
+                    Token::new(TokenKind::BitOr, start, end)
+                }
+            }
+            '^' => Token::new(TokenKind::BitXor, start, end),
+            '~' => Token::new(TokenKind::Tilde, start, end),
+            '-' => {
+                if self.followed_by('=') {
+                    Token::new(TokenKind::MinusAssign, start, self.next_pos)
+                } else {
+                    Token::new(TokenKind::Minus, start, end)
+                }
+            }
+            '*' => {
+                if self.followed_by('=') {
+                    Token::new(TokenKind::StarAssign, start, self.next_pos)
+                } else {
+                    Token::new(TokenKind::Star, start, end)
+                }
+            }
+            '%' => {
+                if self.followed_by('=') {
+                    Token::new(TokenKind::PercentAssign, start, self.next_pos)
+                } else {
+                    Token::new(TokenKind::Percent, start, end)
+                }
+            }
+            '.' if self.peek_char() == '.' => {
+                self.read_char();
+                Token::new(TokenKind::DotDot, start, self.next_pos)
+            }
+            '"' => {
+                let (kind, start, end) = self.parse_string();
+                Token::new(kind, start, end)
+            }
             _ => {
-                if is_letter(self.ch) {
+                if is_ident_start(self.ch) {
                     let (kind, start, end) = self.parse_identifier();
                     // Return directly otherwise we eat the next char since here we have advanced the
                     // cursors, this is due to the fact that read_char() is always called at the end,
                     // was nice debugging xD
                     Token::new(kind, start, end)
                 } else if self.ch.is_ascii_digit() {
-                    let (kind, start, end) = self.parse_integer();
+                    let (kind, start, end) = self.parse_number();
                     // Return directly otherwise we eat the next char since here we have advanced the
                     // cursors, this is due to the fact that read_char() is always called at the end,
                     // was nice debugging xD
                     Token::new(kind, start, end)
                 } else {
                     // Otherwise error out
-                    eprintln!("Unexpected token found: {}", self.ch);
-                    Token::new(TokenKind::Illegal, start, end)
+                    Token::new(
+                        TokenKind::Illegal(LexError::UnexpectedChar(self.ch)),
+                        start,
+                        end,
+                    )
                 }
             }
         };
@@ -165,8 +511,15 @@ impl<'s> Lexer<'s> {
     }
 }
 
-fn is_letter(c: char) -> bool {
-    c.is_ascii_alphabetic() || c == '_'
+/// Whether the char can start an identifier, the chars of XID_Start and `_`
+fn is_ident_start(c: char) -> bool {
+    c == '_' || unicode_ident::is_xid_start(c)
+}
+
+/// Whether the char can follow the start of an identifier, the chars of XID_Continue: letters,
+/// digits, combining marks and connector punctuation like `_`
+fn is_ident_continue(c: char) -> bool {
+    unicode_ident::is_xid_continue(c)
 }
 
 #[cfg(test)]
@@ -177,7 +530,7 @@ mod tests {
 
     #[test]
     fn basic_symbols() {
-        let input = "=+(){},;";
+        let input = "=+(){},;[]:";
 
         let mut lex = Lexer::new(input);
 
@@ -190,6 +543,9 @@ mod tests {
             TokenKind::RightBrace,
             TokenKind::Comma,
             TokenKind::SemiColon,
+            TokenKind::LeftBracket,
+            TokenKind::RightBracket,
+            TokenKind::Colon,
         ];
 
         for expect in expected {
@@ -211,7 +567,7 @@ mod tests {
             };
 
             let result = add(five, ten);
-            !-/*5;
+            !-/ *5;
             5 < 10 > 5;
             if (5 < 10) {
               return true;
@@ -330,4 +686,394 @@ mod tests {
 
         assert_eq!(lex.next_token().kind, TokenKind::Eof)
     }
+
+    #[test]
+    fn strings() {
+        let input = r#""foobar" "foo bar" "" "a\n\t\"b\"\\" "\u{48}\u{1F600}""#;
+
+        let mut lex = Lexer::new(input);
+
+        let expected = vec![
+            TokenKind::String("foobar".to_string()),
+            TokenKind::String("foo bar".to_string()),
+            TokenKind::String("".to_string()),
+            TokenKind::String("a\n\t\"b\"\\".to_string()),
+            TokenKind::String("H\u{1F600}".to_string()),
+        ];
+
+        for expect in expected {
+            let t = lex.next_token();
+            assert_eq!(t.kind, expect);
+        }
+
+        assert_eq!(lex.next_token().kind, TokenKind::Eof);
+    }
+
+    #[test]
+    fn invalid_strings() {
+        // The error is carried by the token and spans the whole literal
+        let cases = [
+            (r#""abc"#, LexError::UnterminatedString, 4),
+            (r#""\q" 5"#, LexError::InvalidEscape, 4),
+            (r#""\u{110000}" 5"#, LexError::InvalidEscape, 12),
+            (r#""a\u{41" 5"#, LexError::InvalidEscape, 8),
+        ];
+
+        for (input, err, end) in cases {
+            let t = Lexer::new(input).next_token();
+            assert_eq!(t.kind, TokenKind::Illegal(err), "input: {input}");
+            assert_eq!((t.span.start, t.span.end), (0, end), "input: {input}");
+        }
+
+        // The rest of an invalid string is skipped
+        let mut lex = Lexer::new(r#""\q" 5"#);
+        lex.next_token();
+        assert_eq!(lex.next_token().kind, TokenKind::Int(5));
+    }
+
+    #[test]
+    fn spans() {
+        let input = "let ab = 10 == \"x\";";
+
+        let mut lex = Lexer::new(input);
+        let mut literals = vec![];
+        loop {
+            let t = lex.next_token();
+            if t.kind == TokenKind::Eof {
+                assert_eq!((t.span.start, t.span.end), (input.len(), input.len()));
+                break;
+            }
+            literals.push(&input[t.span.start..t.span.end]);
+        }
+
+        assert_eq!(literals, vec!["let", "ab", "=", "10", "==", "\"x\"", ";"]);
+    }
+
+    #[test]
+    fn unicode() {
+        let input = "let café = \"¡hola, 世界! 😀\";\u{a0}名前1 _x2 ñ+é cafe\u{301} x\u{203f}y";
+
+        let mut lex = Lexer::new(input);
+        let mut tokens = vec![];
+        loop {
+            let t = lex.next_token();
+            if t.kind == TokenKind::Eof {
+                break;
+            }
+            // Spans must fall on char boundaries
+            tokens.push((t.kind, &input[t.span.start..t.span.end]));
+        }
+
+        let ident = |name: &str| TokenKind::Ident {
+            name: name.to_string(),
+        };
+        let expected = vec![
+            (TokenKind::Let, "let"),
+            (ident("café"), "café"),
+            (TokenKind::Assign, "="),
+            (
+                TokenKind::String("¡hola, 世界! 😀".to_string()),
+                "\"¡hola, 世界! 😀\"",
+            ),
+            (TokenKind::SemiColon, ";"),
+            (ident("名前1"), "名前1"),
+            (ident("_x2"), "_x2"),
+            (ident("ñ"), "ñ"),
+            (TokenKind::Plus, "+"),
+            (ident("é"), "é"),
+            // Combining marks and connector punctuation continue an identifier
+            (ident("cafe\u{301}"), "cafe\u{301}"),
+            (ident("x\u{203f}y"), "x\u{203f}y"),
+        ];
+        assert_eq!(tokens, expected);
+    }
+
+    #[test]
+    fn unicode_illegal() {
+        let input = "1 € 2";
+
+        let mut lex = Lexer::new(input);
+        assert_eq!(lex.next_token().kind, TokenKind::Int(1));
+
+        let t = lex.next_token();
+        assert_eq!(t.kind, TokenKind::Illegal(LexError::UnexpectedChar('€')));
+        assert_eq!(&input[t.span.start..t.span.end], "€");
+
+        assert_eq!(lex.next_token().kind, TokenKind::Int(2));
+    }
+
+    #[test]
+    fn nul_chars() {
+        // A NUL in the source is not the end of the input
+        let input = "1 \0 2 \"a\0b\" // c\0d\n3";
+
+        let mut lex = Lexer::new(input);
+        assert_eq!(lex.next_token().kind, TokenKind::Int(1));
+
+        let t = lex.next_token();
+        assert_eq!(t.kind, TokenKind::Illegal(LexError::UnexpectedChar('\0')));
+        assert_eq!((t.span.start, t.span.end), (2, 3));
+        assert_eq!(
+            LexError::UnexpectedChar('\0').to_string(),
+            "unexpected character `\\0`"
+        );
+
+        assert_eq!(lex.next_token().kind, TokenKind::Int(2));
+        assert_eq!(lex.next_token().kind, TokenKind::String("a\0b".to_string()));
+        assert_eq!(lex.next_token().kind, TokenKind::Int(3));
+        assert_eq!(lex.next_token().kind, TokenKind::Eof);
+    }
+
+    #[test]
+    fn comments() {
+        let input = "1 // one\n/* two /* nested */ still */ 2 /**/ / 3 //";
+        let kinds = |mut lex: Lexer| {
+            let mut kinds = vec![];
+            loop {
+                let t = lex.next_token();
+                if t.kind == TokenKind::Eof {
+                    return kinds;
+                }
+                kinds.push(t.kind);
+            }
+        };
+
+        assert_eq!(
+            kinds(Lexer::new(input)),
+            vec![
+                TokenKind::Int(1),
+                TokenKind::Int(2),
+                TokenKind::Slash,
+                TokenKind::Int(3),
+            ]
+        );
+
+        let comment = |text: &str| TokenKind::Comment(text.to_string());
+        assert_eq!(
+            kinds(Lexer::new(input).with_comments()),
+            vec![
+                TokenKind::Int(1),
+                comment("// one"),
+                comment("/* two /* nested */ still */"),
+                TokenKind::Int(2),
+                comment("/**/"),
+                TokenKind::Slash,
+                TokenKind::Int(3),
+                comment("//"),
+            ]
+        );
+    }
+
+    #[test]
+    fn unterminated_comment() {
+        for input in ["1 /* open", "1 /* a /* b */", "1 /*/"] {
+            let mut lex = Lexer::new(input);
+            lex.next_token();
+
+            let t = lex.next_token();
+            assert_eq!(
+                t.kind,
+                TokenKind::Illegal(LexError::UnterminatedComment),
+                "input: {input}"
+            );
+            assert_eq!((t.span.start, t.span.end), (2, input.len()));
+
+            let eof = lex.next_token();
+            assert_eq!(eof.kind, TokenKind::Eof);
+            assert_eq!((eof.span.start, eof.span.end), (input.len(), input.len()));
+        }
+    }
+
+    #[test]
+    fn integers() {
+        let input = "0 42 1_000_000 0xff 0xDead_Beef 0o17 0b1010_1010 9223372036854775807";
+
+        let mut lex = Lexer::new(input);
+
+        let expected = vec![
+            TokenKind::Int(0),
+            TokenKind::Int(42),
+            TokenKind::Int(1_000_000),
+            TokenKind::Int(0xff),
+            TokenKind::Int(0xdead_beef),
+            TokenKind::Int(0o17),
+            TokenKind::Int(0b1010_1010),
+            TokenKind::Int(i64::MAX),
+        ];
+
+        for expect in expected {
+            let t = lex.next_token();
+            assert_eq!(t.kind, expect);
+        }
+
+        assert_eq!(lex.next_token().kind, TokenKind::Eof);
+    }
+
+    #[test]
+    fn invalid_integers() {
+        let cases = [
+            ("99999999999999999999", LexError::IntegerOverflow),
+            ("0x8000000000000000", LexError::IntegerOverflow),
+            ("0x", LexError::MissingDigits),
+            ("0b__", LexError::MissingDigits),
+            (
+                "0b102",
+                LexError::InvalidDigit {
+                    digit: '2',
+                    radix: 2,
+                },
+            ),
+            (
+                "0o8",
+                LexError::InvalidDigit {
+                    digit: '8',
+                    radix: 8,
+                },
+            ),
+            (
+                "12ab",
+                LexError::InvalidDigit {
+                    digit: 'a',
+                    radix: 10,
+                },
+            ),
+        ];
+
+        for (input, err) in cases {
+            let mut lex = Lexer::new(input);
+
+            let t = lex.next_token();
+            assert_eq!(t.kind, TokenKind::Illegal(err), "input: {input}");
+            // The whole literal is skipped
+            assert_eq!(
+                (t.span.start, t.span.end),
+                (0, input.len()),
+                "input: {input}"
+            );
+            assert_eq!(lex.next_token().kind, TokenKind::Eof);
+        }
+    }
+
+    #[test]
+    fn floats() {
+        let input = "2.75 1e-9 2.5E+3 1_000.000_1 0.5 1..2 1.e";
+
+        let mut lex = Lexer::new(input);
+
+        let expected = vec![
+            TokenKind::Float(2.75),
+            TokenKind::Float(1e-9),
+            TokenKind::Float(2.5e3),
+            TokenKind::Float(1000.0001),
+            TokenKind::Float(0.5),
+            // The dot must be followed by a digit
+            TokenKind::Int(1),
+            TokenKind::DotDot,
+            TokenKind::Int(2),
+            TokenKind::Int(1),
+            TokenKind::Illegal(LexError::UnexpectedChar('.')),
+            TokenKind::Ident {
+                name: "e".to_string(),
+            },
+        ];
+
+        for expect in expected {
+            let t = lex.next_token();
+            assert_eq!(t.kind, expect);
+        }
+
+        assert_eq!(lex.next_token().kind, TokenKind::Eof);
+
+        let invalid = [
+            ("1e400", LexError::FloatOverflow),
+            (
+                "1.5x",
+                LexError::InvalidDigit {
+                    digit: 'x',
+                    radix: 10,
+                },
+            ),
+            (
+                "1e",
+                LexError::InvalidDigit {
+                    digit: 'e',
+                    radix: 10,
+                },
+            ),
+        ];
+        for (input, err) in invalid {
+            let t = Lexer::new(input).next_token();
+            assert_eq!(t.kind, TokenKind::Illegal(err), "input: {input}");
+            assert_eq!(
+                (t.span.start, t.span.end),
+                (0, input.len()),
+                "input: {input}"
+            );
+        }
+    }
+
+    #[test]
+    fn operators() {
+        let input = "<= >= < > % && || & | ^ << >> ~ <<= .. += -= *= /= %= - =";
+
+        let mut lex = Lexer::new(input);
+
+        let expected = vec![
+            TokenKind::LTEq,
+            TokenKind::GTEq,
+            TokenKind::LT,
+            TokenKind::GT,
+            TokenKind::Percent,
+            TokenKind::And,
+            TokenKind::Or,
+            TokenKind::BitAnd,
+            TokenKind::BitOr,
+            TokenKind::BitXor,
+            TokenKind::ShiftLeft,
+            TokenKind::ShiftRight,
+            TokenKind::Tilde,
+            TokenKind::ShiftLeft,
+            TokenKind::Assign,
+            TokenKind::DotDot,
+            TokenKind::PlusAssign,
+            TokenKind::MinusAssign,
+            TokenKind::StarAssign,
+            TokenKind::SlashAssign,
+            TokenKind::PercentAssign,
+            TokenKind::Minus,
+            TokenKind::Assign,
+        ];
+
+        for expect in expected {
+            let t = lex.next_token();
+            assert_eq!(t.kind, expect);
+        }
+
+        assert_eq!(lex.next_token().kind, TokenKind::Eof);
+    }
+
+    #[test]
+    fn loop_keywords() {
+        let input = "while for in break continue inner";
+
+        let mut lex = Lexer::new(input);
+
+        let expected = vec![
+            TokenKind::While,
+            TokenKind::For,
+            TokenKind::In,
+            TokenKind::Break,
+            TokenKind::Continue,
+            TokenKind::Ident {
+                name: "inner".to_string(),
+            },
+        ];
+
+        for expect in expected {
+            let t = lex.next_token();
+            assert_eq!(t.kind, expect);
+        }
+
+        assert_eq!(lex.next_token().kind, TokenKind::Eof);
+    }
 }
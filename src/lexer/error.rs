@@ -0,0 +1,43 @@
+use std::fmt::Display;
+
+/// Reasons why the lexer could not turn a piece of source into a token, they are carried by the
+/// illegal token so the parser can report them
+#[derive(Debug, Clone, PartialEq)]
+pub enum LexError {
+    /// No token starts with the char
+    UnexpectedChar(char),
+    /// The input ended before the closing quote of a string
+    UnterminatedString,
+    /// A backslash in a string is not followed by a known escape
+    InvalidEscape,
+    /// The input ended before the closing `*/` of a block comment
+    UnterminatedComment,
+    /// The integer literal does not fit in 64 bits
+    IntegerOverflow,
+    /// The integer literal has a digit its base does not allow
+    InvalidDigit { digit: char, radix: u32 },
+    /// A base prefix like 0x is not followed by any digit
+    MissingDigits,
+    /// The float literal is too large to be represented
+    FloatOverflow,
+}
+
+impl Display for LexError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            // Escaped so control chars like a NUL can be seen
+            LexError::UnexpectedChar(c) => write!(f, "unexpected character `{}`", c.escape_debug()),
+            LexError::UnterminatedString => write!(f, "unterminated string literal"),
+            LexError::InvalidEscape => write!(f, "invalid escape sequence in string literal"),
+            LexError::UnterminatedComment => write!(f, "unterminated block comment"),
+            LexError::IntegerOverflow => write!(f, "integer literal is too large"),
+            LexError::InvalidDigit { digit, radix } => {
+                write!(f, "invalid digit `{digit}` in a base {radix} literal")
+            }
+            LexError::MissingDigits => write!(f, "missing digits after the base prefix"),
+            LexError::FloatOverflow => write!(f, "float literal is too large"),
+        }
+    }
+}
+
+impl std::error::Error for LexError {}
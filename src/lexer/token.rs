@@ -1,11 +1,14 @@
 use std::fmt::Display;
 
+use super::error::LexError;
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct Token {
     pub kind: TokenKind,
     pub span: Span,
 }
 
+/// Byte range of a node in the source, the end is exclusive
 #[derive(Debug, Clone, PartialEq)]
 pub struct Span {
     pub start: usize,
@@ -35,34 +38,63 @@ impl Token {
 #[derive(Debug, Clone, PartialEq)]
 pub enum TokenKind {
     Eof,
-    Illegal,
+    Illegal(LexError),
+    /// Trivia, only produced when the lexer keeps the comments
+    Comment(String),
 
     // Identifiers and literals
-    Ident { name: String },
+    Ident {
+        name: String,
+    },
     Int(i64),
+    Float(f64),
     String(String),
 
     // Operators
-    Assign, // -
-    Plus,   // +
-    Bang,   // !
-    Minus,  // -
-    Slash,  // /
-    Star,   // *
+    Assign,  // -
+    Plus,    // +
+    Bang,    // !
+    Minus,   // -
+    Slash,   // /
+    Star,    // *
+    Percent, // %
 
-    LT, // <
-    GT, // >
+    LT,   // <
+    GT,   // >
+    LTEq, // <=
+    GTEq, // >=
 
     Eq,    // ==
     NotEq, // !=
 
+    PlusAssign,    // +=
+    MinusAssign,   // -=
+    StarAssign,    // *=
+    SlashAssign,   // /=
+    PercentAssign, // %=
+
+    And, // &&
+    Or,  // ||
+
+    BitAnd,     // &
+    BitOr,      // |
+    BitXor,     // ^
+    ShiftLeft,  // <<
+    ShiftRight, // >>
+    Tilde,      // ~
+
+    DotDot, // ..
+
     // Delimiters
     LeftParen,
     RightParen,
     LeftBrace,
     RightBrace,
+    LeftBracket,
+    RightBracket,
     Comma,
     SemiColon,
+    Colon,
 
     //Keyword
     Let,
@@ -72,23 +104,34 @@ pub enum TokenKind {
     If,
     Else,
     Return,
+    While,
+    For,
+    In,
+    Break,
+    Continue,
 }
 
 impl Display for TokenKind {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             TokenKind::Eof => write!(f, "EOF"),
-            TokenKind::Illegal => write!(f, "ILLEGAL"),
+            TokenKind::Illegal(_) => write!(f, "ILLEGAL"),
+            TokenKind::Comment(text) => write!(f, "{text}"),
             TokenKind::Assign => write!(f, "="),
             TokenKind::Plus => write!(f, "+"),
             TokenKind::LeftParen => write!(f, "("),
             TokenKind::RightParen => write!(f, ")"),
             TokenKind::LeftBrace => write!(f, "{{"),
             TokenKind::RightBrace => write!(f, "}}"),
+            TokenKind::LeftBracket => write!(f, "["),
+            TokenKind::RightBracket => write!(f, "]"),
             TokenKind::Comma => write!(f, ","),
             TokenKind::SemiColon => write!(f, ";"),
+            TokenKind::Colon => write!(f, ":"),
             TokenKind::Ident { name } => write!(f, "{name}"),
             TokenKind::Int(i) => write!(f, "{i}"),
+            // Debug keeps the fraction of round floats, 1.0 is not shown as 1
+            TokenKind::Float(float) => write!(f, "{float:?}"),
             TokenKind::String(s) => write!(f, "{s}"),
             TokenKind::Let => write!(f, "let"),
             TokenKind::Fn => write!(f, "fn"),
@@ -105,6 +148,42 @@ impl Display for TokenKind {
             TokenKind::Star => write!(f, "*"),
             TokenKind::Eq => write!(f, "=="),
             TokenKind::NotEq => write!(f, "!="),
+            TokenKind::Percent => write!(f, "%"),
+            TokenKind::LTEq => write!(f, "<="),
+            TokenKind::GTEq => write!(f, ">="),
+            TokenKind::And => write!(f, "&&"),
+            TokenKind::Or => write!(f, "||"),
+            TokenKind::BitAnd => write!(f, "&"),
+            TokenKind::BitOr => write!(f, "|"),
+            TokenKind::BitXor => write!(f, "^"),
+            TokenKind::ShiftLeft => write!(f, "<<"),
+            TokenKind::ShiftRight => write!(f, ">>"),
+            TokenKind::Tilde => write!(f, "~"),
+            TokenKind::DotDot => write!(f, ".."),
+            TokenKind::PlusAssign => write!(f, "+="),
+            TokenKind::MinusAssign => write!(f, "-="),
+            TokenKind::StarAssign => write!(f, "*="),
+            TokenKind::SlashAssign => write!(f, "/="),
+            TokenKind::PercentAssign => write!(f, "%="),
+            TokenKind::While => write!(f, "while"),
+            TokenKind::For => write!(f, "for"),
+            TokenKind::In => write!(f, "in"),
+            TokenKind::Break => write!(f, "break"),
+            TokenKind::Continue => write!(f, "continue"),
+        }
+    }
+}
+
+impl TokenKind {
+    /// Returns the operator applied by a compound assignment, `+` for `+=`
+    pub fn compound_operator(&self) -> Option<TokenKind> {
+        match self {
+            TokenKind::PlusAssign => Some(TokenKind::Plus),
+            TokenKind::MinusAssign => Some(TokenKind::Minus),
+            TokenKind::StarAssign => Some(TokenKind::Star),
+            TokenKind::SlashAssign => Some(TokenKind::Slash),
+            TokenKind::PercentAssign => Some(TokenKind::Percent),
+            _ => None,
         }
     }
 }
@@ -118,6 +197,11 @@ pub fn lookup_identifier(identifier: &str) -> TokenKind {
         "true" => TokenKind::True,
         "false" => TokenKind::False,
         "return" => TokenKind::Return,
+        "while" => TokenKind::While,
+        "for" => TokenKind::For,
+        "in" => TokenKind::In,
+        "break" => TokenKind::Break,
+        "continue" => TokenKind::Continue,
         _ => TokenKind::Ident {
             name: identifier.to_string(),
         },
@@ -1,33 +1,208 @@
+mod compiler;
+mod diagnostics;
+mod eval;
 mod lexer;
+mod mkc;
+mod object;
 mod parser;
+mod repl;
+mod source_map;
+mod vm;
 
-use std::io::stdin;
+use std::{
+    env, fs,
+    io::{stdin, stdout},
+    path::Path,
+    process::ExitCode,
+    thread,
+};
 
-use lexer::{Lexer, token};
+use compiler::{Bytecode, Compiler, disasm::disassemble};
+use diagnostics::Diagnostic;
+use lexer::Lexer;
+use mkc::CompiledScript;
+use parser::Parser;
+use source_map::SourceFile;
+use vm::Vm;
 
-fn main() {
-    println!("Welcome to the Monkey REPL!");
+const USAGE: &str = "usage: monkey-rs [run <file> | compile <file> [<output>] | disasm <file>]";
 
-    loop {
-        let mut input = String::new();
-        stdin().read_line(&mut input).unwrap();
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
 
-        if input.trim_end().is_empty() {
-            std::process::exit(0);
+    match args.as_slice() {
+        [] => start_repl(),
+        [command, path] if command == "run" => run(path),
+        [command, path] if command == "compile" => {
+            compile(path, &Path::new(path).with_extension("mkc"))
         }
+        [command, path, output] if command == "compile" => compile(path, Path::new(output)),
+        [command, path] if command == "disasm" => disasm(path),
+        _ => {
+            eprintln!("{USAGE}");
+            ExitCode::from(2)
+        }
+    }
+}
+
+fn start_repl() -> ExitCode {
+    println!("Welcome to the Monkey REPL! Type :help for the available commands");
+
+    // The evaluator needs a bigger stack than the main thread has for deep recursion
+    let repl = thread::Builder::new()
+        .stack_size(eval::STACK_SIZE)
+        .spawn(|| repl::start(stdin().lock(), stdout()));
+    match repl.map(|handle| handle.join()) {
+        Ok(Ok(Ok(()))) => ExitCode::SUCCESS,
+        Ok(Ok(Err(e))) => {
+            eprintln!("REPL error: {e}");
+            ExitCode::FAILURE
+        }
+        // The panic was already reported by the thread
+        Ok(Err(_)) => ExitCode::FAILURE,
+        Err(e) => {
+            eprintln!("error: could not start the REPL: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// A script given on the command line
+enum Script {
+    Source(SourceFile),
+    /// Loaded from a `.mkc` file, its source is not parsed
+    Compiled(CompiledScript),
+}
+
+/// Reads a script or a compiled script, errors are printed
+fn read_script(path: &str) -> Option<Script> {
+    let bytes = match fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            eprintln!("error: could not read {path}: {e}");
+            return None;
+        }
+    };
+
+    if mkc::is_compiled(&bytes) {
+        return match CompiledScript::from_bytes(&bytes) {
+            Ok(script) => Some(Script::Compiled(script)),
+            Err(e) => {
+                eprintln!("error: could not load {path}: {e}");
+                None
+            }
+        };
+    }
+    match String::from_utf8(bytes) {
+        Ok(source) => Some(Script::Source(SourceFile::new(path, source))),
+        Err(_) => {
+            eprintln!("error: {path} is neither a script nor a compiled script");
+            None
+        }
+    }
+}
+
+/// Runs a script on the vm
+fn run(path: &str) -> ExitCode {
+    let file = match read_script(path) {
+        Some(Script::Source(file)) => file,
+        Some(Script::Compiled(script)) => return run_compiled(script),
+        None => return ExitCode::FAILURE,
+    };
+    let Some(bytecode) = compile_source(&file) else {
+        return ExitCode::FAILURE;
+    };
+
+    match Vm::new(bytecode).run() {
+        Ok(_) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprint!("{}", Diagnostic::from(&err).render(&file));
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run_compiled(script: CompiledScript) -> ExitCode {
+    let Err(err) = Vm::new(script.bytecode).run() else {
+        return ExitCode::SUCCESS;
+    };
+    // The source is only read to show where the error is, if it changed the spans are wrong
+    match fs::read_to_string(&script.source_name) {
+        Ok(source) if mkc::checksum(source.as_bytes()) == script.source_checksum => {
+            let file = SourceFile::new(script.source_name, source);
+            eprint!("{}", Diagnostic::from(&err).render(&file));
+        }
+        _ => {
+            let span = err.span();
+            eprintln!("error: {err}");
+            eprintln!(
+                " --> {} at bytes {}..{}",
+                script.source_name, span.start, span.end
+            );
+        }
+    }
+    ExitCode::FAILURE
+}
+
+/// Prints the instructions of a script, scripts that are not compiled yet are compiled first
+fn disasm(path: &str) -> ExitCode {
+    let bytecode = match read_script(path) {
+        Some(Script::Source(file)) => compile_source(&file),
+        Some(Script::Compiled(script)) => Some(script.bytecode),
+        None => None,
+    };
+    let Some(bytecode) = bytecode else {
+        return ExitCode::FAILURE;
+    };
+
+    print!("{}", disassemble(&bytecode));
+    ExitCode::SUCCESS
+}
 
-        let mut l = Lexer::new(&input);
-        loop {
-            let t = l.next_token();
-            if t.kind == token::TokenKind::Eof {
-                break;
-            } else {
-                println!(
-                    "token: {}, literal value: {}",
-                    t,
-                    &input[t.span.start..=t.span.end]
-                )
+/// Writes the compiled script to the output, it is run with the run command
+fn compile(path: &str, output: &Path) -> ExitCode {
+    let Some(file) = read_source(path) else {
+        return ExitCode::FAILURE;
+    };
+    let Some(bytecode) = compile_source(&file) else {
+        return ExitCode::FAILURE;
+    };
+
+    let bytes = CompiledScript::new(&file, bytecode).to_bytes();
+    if let Err(e) = fs::write(output, bytes) {
+        eprintln!("error: could not write {}: {e}", output.display());
+        return ExitCode::FAILURE;
+    }
+    ExitCode::SUCCESS
+}
+
+fn read_source(path: &str) -> Option<SourceFile> {
+    match fs::read_to_string(path) {
+        Ok(source) => Some(SourceFile::new(path, source)),
+        Err(e) => {
+            eprintln!("error: could not read {path}: {e}");
+            None
+        }
+    }
+}
+
+/// Parses and compiles the source, errors are printed as diagnostics
+fn compile_source(file: &SourceFile) -> Option<Bytecode> {
+    let program = match Parser::new(Lexer::new(file.source())).parse_program() {
+        Ok(program) => program,
+        Err(errs) => {
+            for err in errs.iter() {
+                eprint!("{}", Diagnostic::from(err).render(file));
             }
+            return None;
+        }
+    };
+
+    match Compiler::new().compile(&program) {
+        Ok(bytecode) => Some(bytecode),
+        Err(err) => {
+            eprint!("{}", Diagnostic::from(&err).render(file));
+            None
         }
     }
 }
@@ -0,0 +1,681 @@
+pub mod error;
+
+use std::{collections::HashMap, rc::Rc};
+
+use error::LoadError;
+
+use crate::{
+    compiler::{
+        Bytecode,
+        code::{Opcode, read_operands, stack_use},
+    },
+    lexer::token::Span,
+    object::{Capture, CompiledFunction, Object},
+    source_map::SourceFile,
+};
+
+pub const MAGIC: [u8; 4] = *b"MKC\0";
+/// Bumped whenever the encoding or the instruction set changes, files of other versions are
+/// rejected
+pub const VERSION: u16 = 3;
+const HEADER_LEN: usize = MAGIC.len() + 2 + 4;
+
+const TAG_INTEGER: u8 = 0;
+const TAG_FLOAT: u8 = 1;
+const TAG_STRING: u8 = 2;
+const TAG_FUNCTION: u8 = 3;
+
+const CAPTURE_LOCAL: u8 = 0;
+const CAPTURE_FREE: u8 = 1;
+
+/// A compiled script as stored in a `.mkc` file
+///
+/// A file is a header followed by the body, the integers are big-endian:
+/// - header: the magic bytes, the `u16` version of the format and the `u32` checksum of the body
+/// - body: the name and the checksum of the source, the names of the globals, the main function
+///   then the constant pool as a count followed by the tagged constants
+/// - function: the number of parameters and of locals, the tagged captures, the instructions and
+///   the span table
+///
+/// The builtins are referred to by the names of the globals so the file does not depend on their
+/// order
+///
+/// Counts, lengths and offsets are `u32` and strings are UTF-8 prefixed with their length
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompiledScript {
+    /// Name of the source file the spans of the bytecode point into
+    pub source_name: String,
+    /// Checksum of the source, tells whether the spans still match the file
+    pub source_checksum: u32,
+    pub bytecode: Bytecode,
+}
+
+impl CompiledScript {
+    pub fn new(source: &SourceFile, bytecode: Bytecode) -> Self {
+        Self {
+            source_name: source.name().to_string(),
+            source_checksum: checksum(source.source().as_bytes()),
+            bytecode,
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut body = Encoder::default();
+        body.string(&self.source_name);
+        body.u32(self.source_checksum);
+        body.usize(self.bytecode.globals.len());
+        for name in self.bytecode.globals.iter() {
+            body.string(name);
+        }
+        body.function(&self.bytecode.main);
+        body.usize(self.bytecode.constants.len());
+        for constant in self.bytecode.constants.iter() {
+            body.constant(constant);
+        }
+
+        let mut bytes = Vec::with_capacity(HEADER_LEN + body.bytes.len());
+        bytes.extend_from_slice(&MAGIC);
+        bytes.extend_from_slice(&VERSION.to_be_bytes());
+        bytes.extend_from_slice(&checksum(&body.bytes).to_be_bytes());
+        bytes.extend_from_slice(&body.bytes);
+        bytes
+    }
+
+    /// Loads a script, the file is checked so the vm can run it without reading out of bounds or
+    /// taking more values than the stack holds
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, LoadError> {
+        if !is_compiled(bytes) {
+            return Err(LoadError::NotCompiled);
+        }
+        let mut header = Decoder::new(&bytes[MAGIC.len()..]);
+        let version = u16::from_be_bytes([header.u8()?, header.u8()?]);
+        if version != VERSION {
+            return Err(LoadError::UnsupportedVersion {
+                found: version,
+                expected: VERSION,
+            });
+        }
+        let expected = header.u32()?;
+
+        let body = &bytes[HEADER_LEN..];
+        let found = checksum(body);
+        if found != expected {
+            return Err(LoadError::ChecksumMismatch { found, expected });
+        }
+
+        let mut body = Decoder::new(body);
+        let source_name = body.string()?;
+        let source_checksum = body.u32()?;
+        let globals = (0..body.usize()?)
+            .map(|_| body.string())
+            .collect::<Result<Vec<_>, _>>()?;
+        let main = Rc::new(body.function()?);
+        let constants = (0..body.usize()?)
+            .map(|_| body.constant())
+            .collect::<Result<Vec<_>, _>>()?;
+        if body.offset != body.bytes.len() {
+            return Err(malformed(format!(
+                "{} unexpected bytes after the constants",
+                body.bytes.len() - body.offset
+            )));
+        }
+
+        if !main.captures.is_empty() {
+            return Err(malformed("main function with captures".to_string()));
+        }
+        validate(&main, &constants, globals.len())?;
+        for constant in constants.iter() {
+            if let Object::CompiledFunction(function) = constant {
+                validate(function, &constants, globals.len())?;
+            }
+        }
+
+        Ok(Self {
+            source_name,
+            source_checksum,
+            bytecode: Bytecode {
+                main,
+                constants,
+                globals,
+            },
+        })
+    }
+}
+
+/// Whether the bytes start like a compiled script, they may still fail to load
+pub fn is_compiled(bytes: &[u8]) -> bool {
+    bytes.starts_with(&MAGIC)
+}
+
+/// 32 bits FNV-1a hash
+pub fn checksum(bytes: &[u8]) -> u32 {
+    bytes.iter().fold(0x811c9dc5, |hash, byte| {
+        (hash ^ *byte as u32).wrapping_mul(0x01000193)
+    })
+}
+
+fn malformed(reason: String) -> LoadError {
+    LoadError::Malformed(reason)
+}
+
+/// Checks the instructions decode, their operands point at existing constants, variables and
+/// instructions, and the stack holds the values each of them takes
+fn validate(
+    function: &CompiledFunction,
+    constants: &[Object],
+    num_globals: usize,
+) -> Result<(), LoadError> {
+    if function.num_parameters > function.num_locals {
+        return Err(malformed(format!(
+            "function with {} parameters and {} locals",
+            function.num_parameters, function.num_locals
+        )));
+    }
+
+    // Decoded first so the jumps can be checked against the start of the instructions
+    let instructions = &function.instructions;
+    let mut decoded = vec![];
+    let mut offset = 0;
+    while offset < instructions.len() {
+        let op = Opcode::from_byte(instructions[offset]).ok_or_else(|| {
+            malformed(format!(
+                "unknown opcode {} at {offset}",
+                instructions[offset]
+            ))
+        })?;
+        let def = op.definition();
+        let width: usize = def.operand_widths.iter().sum();
+        let Some(bytes) = instructions.get(offset + 1..offset + 1 + width) else {
+            return Err(malformed(format!("truncated {op} at {offset}")));
+        };
+
+        let (operands, _) = read_operands(&def, bytes);
+        decoded.push(Instruction {
+            offset,
+            op,
+            operands,
+        });
+        offset += 1 + width;
+    }
+    let starts: HashMap<usize, usize> = decoded
+        .iter()
+        .enumerate()
+        .map(|(index, instruction)| (instruction.offset, index))
+        .collect();
+
+    for Instruction {
+        offset,
+        op,
+        operands,
+    } in decoded.iter()
+    {
+        let is_local = |slot: usize| slot < function.num_locals;
+        let is_free = |index: usize| index < function.captures.len();
+        let is_target = |target: usize| starts.contains_key(&target);
+        let valid = match op {
+            Opcode::Constant => operands[0] < constants.len(),
+            Opcode::Fail => matches!(constants.get(operands[0]), Some(Object::String(_))),
+            // The closure captures variables of the function creating it
+            Opcode::Closure => match constants.get(operands[0]) {
+                Some(Object::CompiledFunction(closure)) => {
+                    closure.captures.iter().all(|capture| match *capture {
+                        Capture::Local(slot) => is_local(slot),
+                        Capture::Free(index) => is_free(index),
+                    })
+                }
+                _ => false,
+            },
+            Opcode::Jump | Opcode::JumpNotTruthy | Opcode::IterNext => is_target(operands[0]),
+            Opcode::GetGlobal | Opcode::SetGlobal | Opcode::AssignGlobal | Opcode::CheckGlobal => {
+                operands[0] < num_globals
+            }
+            Opcode::GetLocal | Opcode::SetLocal => is_local(operands[0]),
+            Opcode::GetFree | Opcode::SetFree => is_free(operands[0]),
+            Opcode::TryGetLocal | Opcode::TrySetLocal => {
+                is_local(operands[0]) && is_target(operands[1])
+            }
+            Opcode::TryGetFree | Opcode::TrySetFree => {
+                is_free(operands[0]) && is_target(operands[1])
+            }
+            Opcode::NewLocals => operands[0] <= operands[1] && is_local(operands[1]),
+            _ => true,
+        };
+        if !valid {
+            return Err(malformed(format!(
+                "invalid operand {} of {op} at {offset}",
+                operands[0]
+            )));
+        }
+    }
+
+    check_stack(&decoded, &starts)
+}
+
+/// An instruction of a function being validated
+struct Instruction {
+    offset: usize,
+    op: Opcode,
+    operands: Vec<usize>,
+}
+
+/// Follows every path from the first instruction, each instruction must find the values it takes
+/// on the stack, the paths reaching an instruction must leave the stack as deep and none may run
+/// past the last instruction
+fn check_stack(decoded: &[Instruction], starts: &HashMap<usize, usize>) -> Result<(), LoadError> {
+    let mut depths = vec![None; decoded.len()];
+    // Instructions to visit with the depth of the stack before them
+    let mut pending = vec![(0, 0)];
+
+    while let Some((index, depth)) = pending.pop() {
+        let Some(Instruction {
+            offset,
+            op,
+            operands,
+        }) = decoded.get(index)
+        else {
+            return Err(malformed(
+                "function without a return at its end".to_string(),
+            ));
+        };
+        match depths[index] {
+            Some(known) if known == depth => continue,
+            Some(known) => {
+                return Err(malformed(format!(
+                    "stack depth {depth} instead of {known} at {offset}"
+                )));
+            }
+            None => depths[index] = Some(depth),
+        }
+
+        let (taken, left) = stack_use(*op, operands);
+        if depth < taken {
+            return Err(malformed(format!(
+                "{op} at {offset} takes {taken} values from a stack of {depth}"
+            )));
+        }
+        let next = depth - taken + left;
+        let target = |operand: usize| starts[&operands[operand]];
+        match op {
+            Opcode::Return | Opcode::ReturnValue | Opcode::Fail => {}
+            Opcode::Jump => pending.push((target(0), next)),
+            Opcode::JumpNotTruthy => pending.extend([(index + 1, next), (target(0), next)]),
+            // The loop ends without pushing an item
+            Opcode::IterNext => pending.extend([(index + 1, next), (target(0), next - 1)]),
+            // The try instructions push or pop a value when they jump
+            Opcode::TryGetLocal | Opcode::TryGetFree => {
+                pending.extend([(index + 1, next), (target(1), next + 1)])
+            }
+            Opcode::TrySetLocal | Opcode::TrySetFree => {
+                pending.extend([(index + 1, next), (target(1), next - 1)])
+            }
+            _ => pending.push((index + 1, next)),
+        }
+    }
+
+    Ok(())
+}
+
+#[derive(Default)]
+struct Encoder {
+    bytes: Vec<u8>,
+}
+
+impl Encoder {
+    fn u8(&mut self, value: u8) {
+        self.bytes.push(value);
+    }
+
+    fn u32(&mut self, value: u32) {
+        self.bytes.extend_from_slice(&value.to_be_bytes());
+    }
+
+    fn usize(&mut self, value: usize) {
+        let value = u32::try_from(value).expect("Compiled code is smaller than 4 GiB");
+        self.u32(value);
+    }
+
+    fn string(&mut self, value: &str) {
+        self.usize(value.len());
+        self.bytes.extend_from_slice(value.as_bytes());
+    }
+
+    fn function(&mut self, function: &CompiledFunction) {
+        self.usize(function.num_parameters);
+        self.usize(function.num_locals);
+        self.usize(function.captures.len());
+        for capture in function.captures.iter() {
+            let (tag, index) = match *capture {
+                Capture::Local(slot) => (CAPTURE_LOCAL, slot),
+                Capture::Free(index) => (CAPTURE_FREE, index),
+            };
+            self.u8(tag);
+            self.usize(index);
+        }
+        self.usize(function.instructions.len());
+        self.bytes.extend_from_slice(&function.instructions);
+
+        self.usize(function.spans.len());
+        for (offset, span) in function.spans.iter() {
+            self.usize(*offset);
+            self.usize(span.start);
+            self.usize(span.end);
+        }
+    }
+
+    fn constant(&mut self, constant: &Object) {
+        match constant {
+            Object::Integer(value) => {
+                self.u8(TAG_INTEGER);
+                self.bytes.extend_from_slice(&value.to_be_bytes());
+            }
+            Object::Float(value) => {
+                self.u8(TAG_FLOAT);
+                self.bytes.extend_from_slice(&value.to_bits().to_be_bytes());
+            }
+            Object::String(value) => {
+                self.u8(TAG_STRING);
+                self.string(value);
+            }
+            Object::CompiledFunction(function) => {
+                self.u8(TAG_FUNCTION);
+                self.function(function);
+            }
+            other => unreachable!(
+                "The compiler only adds literals and functions to the constant pool, not {}",
+                other.type_name()
+            ),
+        }
+    }
+}
+
+struct Decoder<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Decoder<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, offset: 0 }
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], LoadError> {
+        let bytes = self
+            .bytes
+            .get(self.offset..self.offset.saturating_add(len))
+            .ok_or(LoadError::Truncated)?;
+        self.offset += len;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, LoadError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32, LoadError> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn u64(&mut self) -> Result<u64, LoadError> {
+        let mut value = [0; 8];
+        value.copy_from_slice(self.take(8)?);
+        Ok(u64::from_be_bytes(value))
+    }
+
+    fn usize(&mut self) -> Result<usize, LoadError> {
+        Ok(self.u32()? as usize)
+    }
+
+    fn string(&mut self) -> Result<String, LoadError> {
+        let len = self.usize()?;
+        let bytes = self.take(len)?;
+        String::from_utf8(bytes.to_vec()).map_err(|_| malformed("invalid UTF-8 string".to_string()))
+    }
+
+    fn function(&mut self) -> Result<CompiledFunction, LoadError> {
+        let num_parameters = self.usize()?;
+        let num_locals = self.usize()?;
+        let mut captures = vec![];
+        for _ in 0..self.usize()? {
+            let capture = match self.u8()? {
+                CAPTURE_LOCAL => Capture::Local(self.usize()?),
+                CAPTURE_FREE => Capture::Free(self.usize()?),
+                tag => return Err(malformed(format!("unknown capture tag {tag}"))),
+            };
+            captures.push(capture);
+        }
+        let len = self.usize()?;
+        let instructions = self.take(len)?.to_vec();
+
+        let mut spans = vec![];
+        for _ in 0..self.usize()? {
+            let offset = self.usize()?;
+            let start = self.usize()?;
+            let end = self.usize()?;
+            spans.push((offset, Span { start, end }));
+        }
+
+        Ok(CompiledFunction {
+            instructions,
+            spans,
+            num_locals,
+            num_parameters,
+            captures,
+        })
+    }
+
+    fn constant(&mut self) -> Result<Object, LoadError> {
+        match self.u8()? {
+            TAG_INTEGER => Ok(Object::Integer(self.u64()? as i64)),
+            TAG_FLOAT => Ok(Object::Float(f64::from_bits(self.u64()?))),
+            TAG_STRING => Ok(Object::String(self.string()?)),
+            TAG_FUNCTION => Ok(Object::CompiledFunction(Rc::new(self.function()?))),
+            tag => Err(malformed(format!("unknown constant tag {tag}"))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{
+        compiler::{Compiler, code::make},
+        lexer::Lexer,
+        parser::Parser,
+        vm::Vm,
+    };
+
+    fn compile(input: &str) -> CompiledScript {
+        let program = Parser::new(Lexer::new(input)).parse_program().unwrap();
+        let bytecode = Compiler::new().compile(&program).unwrap();
+        CompiledScript::new(&SourceFile::new("test.mk", input), bytecode)
+    }
+
+    /// Builds a file of the current version around the body
+    fn with_body(body: &[u8]) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend_from_slice(&VERSION.to_be_bytes());
+        bytes.extend_from_slice(&checksum(body).to_be_bytes());
+        bytes.extend_from_slice(body);
+        bytes
+    }
+
+    const PROGRAM: &str = r#"
+        let greet = fn(name) { "Hello " + name };
+        let adder = fn(a) { fn(b) { a + b + 0.5 } };
+        let sum = 0;
+        for (i in 0..4) { sum += adder(i)(-1); }
+        [greet("you"), sum]
+    "#;
+
+    #[test]
+    fn round_trip() {
+        let script = compile(PROGRAM);
+        let bytes = script.to_bytes();
+        assert!(is_compiled(&bytes));
+
+        let loaded = CompiledScript::from_bytes(&bytes).unwrap();
+        assert_eq!(loaded, script);
+        assert_eq!(loaded.source_name, "test.mk");
+        assert_eq!(loaded.source_checksum, checksum(PROGRAM.as_bytes()));
+
+        assert_eq!(
+            Vm::new(loaded.bytecode).run(),
+            Vm::new(script.bytecode).run()
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_files() {
+        let bytes = compile(PROGRAM).to_bytes();
+
+        let mut other_version = bytes.clone();
+        other_version[4..6].copy_from_slice(&(VERSION + 1).to_be_bytes());
+
+        let mut corrupted = bytes.clone();
+        let last = corrupted.len() - 1;
+        corrupted[last] ^= 1;
+
+        let mut trailing = bytes[HEADER_LEN..].to_vec();
+        trailing.push(0);
+
+        let cases = [
+            (PROGRAM.as_bytes().to_vec(), LoadError::NotCompiled),
+            (
+                other_version,
+                LoadError::UnsupportedVersion {
+                    found: VERSION + 1,
+                    expected: VERSION,
+                },
+            ),
+            (bytes[..HEADER_LEN - 1].to_vec(), LoadError::Truncated),
+            (
+                with_body(&bytes[HEADER_LEN..bytes.len() - 1]),
+                LoadError::Truncated,
+            ),
+            (
+                with_body(&trailing),
+                malformed("1 unexpected bytes after the constants".to_string()),
+            ),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(CompiledScript::from_bytes(&input), Err(expected));
+        }
+
+        assert!(matches!(
+            CompiledScript::from_bytes(&corrupted),
+            Err(LoadError::ChecksumMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn rejects_invalid_instructions() {
+        let function = |instructions: Vec<u8>| {
+            let mut body = Encoder::default();
+            body.string("test.mk");
+            body.u32(0);
+            // No globals
+            body.usize(0);
+            body.function(&CompiledFunction {
+                instructions,
+                num_locals: 1,
+                ..Default::default()
+            });
+            body.usize(1);
+            body.constant(&Object::Integer(1));
+            with_body(&body.bytes)
+        };
+
+        let cases = [
+            (vec![255], "unknown opcode 255 at 0"),
+            (
+                vec![Opcode::Pop as u8, Opcode::Jump as u8, 0],
+                "truncated OpJump at 1",
+            ),
+            (
+                vec![Opcode::Constant as u8, 0, 1],
+                "invalid operand 1 of OpConstant at 0",
+            ),
+            (
+                vec![Opcode::Closure as u8, 0, 0],
+                "invalid operand 0 of OpClosure at 0",
+            ),
+            // The message must be a string
+            (
+                vec![Opcode::Fail as u8, 0, 0],
+                "invalid operand 0 of OpFail at 0",
+            ),
+            (
+                vec![Opcode::GetGlobal as u8, 0, 0],
+                "invalid operand 0 of OpGetGlobal at 0",
+            ),
+            (
+                vec![Opcode::GetFree as u8, 0, Opcode::ReturnValue as u8],
+                "invalid operand 0 of OpGetFree at 0",
+            ),
+            // Into the operand of the constant
+            (
+                vec![Opcode::Constant as u8, 0, 0, Opcode::Jump as u8, 0, 1],
+                "invalid operand 1 of OpJump at 3",
+            ),
+            (
+                vec![Opcode::Pop as u8, Opcode::ReturnValue as u8],
+                "OpPop at 0 takes 1 values from a stack of 0",
+            ),
+            (
+                vec![Opcode::True as u8, Opcode::Pop as u8],
+                "function without a return at its end",
+            ),
+            // The jump leaves one value and the other path two
+            (
+                vec![
+                    Opcode::True as u8,
+                    Opcode::True as u8,
+                    Opcode::JumpNotTruthy as u8,
+                    0,
+                    8,
+                    Opcode::Constant as u8,
+                    0,
+                    0,
+                    Opcode::ReturnValue as u8,
+                ],
+                "stack depth 2 instead of 1 at 8",
+            ),
+            (
+                vec![Opcode::Jump as u8, 0, 9],
+                "invalid operand 9 of OpJump at 0",
+            ),
+            (
+                vec![Opcode::GetLocal as u8, 1],
+                "invalid operand 1 of OpGetLocal at 0",
+            ),
+        ];
+        for (instructions, expected) in cases {
+            assert_eq!(
+                CompiledScript::from_bytes(&function(instructions)),
+                Err(malformed(expected.to_string()))
+            );
+        }
+    }
+
+    #[test]
+    fn unset_locals_are_errors() {
+        let mut body = Encoder::default();
+        body.string("test.mk");
+        body.u32(0);
+        body.usize(0);
+        body.function(&CompiledFunction {
+            instructions: [make(Opcode::GetLocal, &[0]), make(Opcode::ReturnValue, &[])].concat(),
+            num_locals: 1,
+            ..Default::default()
+        });
+        body.usize(0);
+
+        let script = CompiledScript::from_bytes(&with_body(&body.bytes)).unwrap();
+        assert_eq!(
+            Vm::new(script.bytecode).run().unwrap_err().to_string(),
+            "variable 0 read before it is set"
+        );
+    }
+}
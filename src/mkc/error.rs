@@ -0,0 +1,36 @@
+use std::fmt::Display;
+
+/// Reasons a `.mkc` file is rejected by the loader
+#[derive(Debug, Clone, PartialEq)]
+pub enum LoadError {
+    /// The file does not start with the magic bytes
+    NotCompiled,
+    /// The file was written by another version of the format
+    UnsupportedVersion { found: u16, expected: u16 },
+    /// The contents do not match the checksum of the header
+    ChecksumMismatch { found: u32, expected: u32 },
+    /// The file ends in the middle of a value
+    Truncated,
+    /// The contents are not valid bytecode
+    Malformed(String),
+}
+
+impl Display for LoadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LoadError::NotCompiled => write!(f, "not a compiled monkey file"),
+            LoadError::UnsupportedVersion { found, expected } => write!(
+                f,
+                "unsupported bytecode version {found}, expected version {expected}, compile the script again"
+            ),
+            LoadError::ChecksumMismatch { found, expected } => write!(
+                f,
+                "corrupted file, checksum is {found:#010x} instead of {expected:#010x}"
+            ),
+            LoadError::Truncated => write!(f, "truncated file"),
+            LoadError::Malformed(reason) => write!(f, "malformed bytecode: {reason}"),
+        }
+    }
+}
+
+impl std::error::Error for LoadError {}
@@ -0,0 +1,382 @@
+pub mod builtins;
+pub mod environment;
+
+use std::{cell::RefCell, collections::HashMap, fmt::Display, rc::Rc};
+
+use builtins::Builtin;
+use environment::Env;
+
+use crate::{
+    compiler::code::{Instructions, SpanTable},
+    parser::ast::{BlockStatement, Ident},
+};
+
+/// Runtime values produced when evaluating a program
+#[derive(Debug, Clone, PartialEq)]
+pub enum Object {
+    Integer(i64),
+    Float(f64),
+    Boolean(bool),
+    String(String),
+    /// Arrays are immutable values, the elements are shared between copies
+    Array(Rc<Vec<Object>>),
+    Hash(Rc<Hash>),
+    /// Integers from the start to the end, the end is excluded
+    Range(i64, i64),
+    Null,
+    /// Wraps the value of a return statement so it can bubble up through nested blocks
+    ReturnValue(Box<Object>),
+    /// Produced by break and continue, they bubble up to the enclosing loop like return values
+    Break,
+    Continue,
+    /// Runtime errors are values too, they stop the evaluation as soon as they are produced
+    Error(String),
+    Function(Function),
+    Builtin(Builtin),
+    /// Body of a function literal, only found in the constant pool of the bytecode
+    CompiledFunction(Rc<CompiledFunction>),
+    /// Function created by the vm, with the variables it captured
+    Closure(Rc<Closure>),
+}
+
+impl Object {
+    /// Null and false are the only falsy values, everything else is truthy
+    pub fn is_truthy(&self) -> bool {
+        !matches!(self, Object::Null | Object::Boolean(false))
+    }
+
+    /// Returns true if the object is an error
+    pub fn is_error(&self) -> bool {
+        matches!(self, Object::Error(_))
+    }
+
+    /// Returns the key used to store the object in a hash, only integers, booleans and strings
+    /// can be used as keys
+    pub fn hash_key(&self) -> Option<HashKey> {
+        match self {
+            Object::Integer(value) => Some(HashKey::Integer(*value)),
+            Object::Boolean(value) => Some(HashKey::Boolean(*value)),
+            Object::String(value) => Some(HashKey::String(value.clone())),
+            _ => None,
+        }
+    }
+
+    /// Returns the name of the type of the object, used when reporting errors
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Object::Integer(_) => "INTEGER",
+            Object::Float(_) => "FLOAT",
+            Object::Boolean(_) => "BOOLEAN",
+            Object::String(_) => "STRING",
+            Object::Array(_) => "ARRAY",
+            Object::Hash(_) => "HASH",
+            Object::Range(..) => "RANGE",
+            Object::Null => "NULL",
+            Object::ReturnValue(_) => "RETURN_VALUE",
+            Object::Break => "BREAK",
+            Object::Continue => "CONTINUE",
+            Object::Error(_) => "ERROR",
+            Object::Function(_) => "FUNCTION",
+            Object::Builtin(_) => "BUILTIN",
+            Object::CompiledFunction(_) => "COMPILED_FUNCTION",
+            // Same as the functions of the evaluator
+            Object::Closure(_) => "FUNCTION",
+        }
+    }
+}
+
+impl Display for Object {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Object::Integer(value) => write!(f, "{value}"),
+            // Debug keeps the fraction of round floats, 1.0 is not shown as 1
+            Object::Float(value) => write!(f, "{value:?}"),
+            Object::Boolean(value) => write!(f, "{value}"),
+            Object::String(value) => write!(f, "{value}"),
+            Object::Array(elements) => {
+                let elements = elements
+                    .iter()
+                    .map(|e| e.to_string())
+                    .collect::<Vec<String>>()
+                    .join(", ");
+                write!(f, "[{elements}]")
+            }
+            Object::Hash(hash) => write!(f, "{hash}"),
+            Object::Range(start, end) => write!(f, "{start}..{end}"),
+            Object::Null => write!(f, "null"),
+            Object::ReturnValue(value) => write!(f, "{value}"),
+            Object::Break => write!(f, "break"),
+            Object::Continue => write!(f, "continue"),
+            Object::Error(message) => write!(f, "ERROR: {message}"),
+            Object::Function(function) => write!(f, "{function}"),
+            Object::Builtin(builtin) => write!(f, "{builtin}"),
+            Object::CompiledFunction(function) => write!(f, "{function}"),
+            Object::Closure(closure) => write!(f, "{}", closure.function),
+        }
+    }
+}
+
+/// Keys of a hash, they are built from the value of the object so two equal objects always map
+/// to the same entry
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum HashKey {
+    Integer(i64),
+    Boolean(bool),
+    String(String),
+}
+
+impl From<HashKey> for Object {
+    fn from(key: HashKey) -> Self {
+        match key {
+            HashKey::Integer(value) => Object::Integer(value),
+            HashKey::Boolean(value) => Object::Boolean(value),
+            HashKey::String(value) => Object::String(value),
+        }
+    }
+}
+
+impl Display for HashKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HashKey::Integer(value) => write!(f, "{value}"),
+            HashKey::Boolean(value) => write!(f, "{value}"),
+            HashKey::String(value) => write!(f, "{value}"),
+        }
+    }
+}
+
+/// A hash map that remembers the insertion order of its keys, so it is always displayed and
+/// iterated the same way
+#[derive(Debug, Clone, Default)]
+pub struct Hash {
+    pairs: Vec<(HashKey, Object)>,
+    index: HashMap<HashKey, usize>,
+}
+
+impl Hash {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get(&self, key: &HashKey) -> Option<&Object> {
+        self.index.get(key).map(|&i| &self.pairs[i].1)
+    }
+
+    /// Inserts the value, if the key is already present the value is replaced in place
+    pub fn insert(&mut self, key: HashKey, value: Object) {
+        match self.index.get(&key) {
+            Some(&i) => self.pairs[i].1 = value,
+            None => {
+                self.index.insert(key.clone(), self.pairs.len());
+                self.pairs.push((key, value));
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.pairs.len()
+    }
+
+    /// Iterates over the pairs in insertion order
+    pub fn iter(&self) -> impl Iterator<Item = &(HashKey, Object)> {
+        self.pairs.iter()
+    }
+}
+
+// Two hashes are equal if they hold the same pairs, whatever the order they were inserted in
+impl PartialEq for Hash {
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len()
+            && self
+                .iter()
+                .all(|(key, value)| other.get(key) == Some(value))
+    }
+}
+
+impl Display for Hash {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let pairs = self
+            .iter()
+            .map(|(key, value)| format!("{key}: {value}"))
+            .collect::<Vec<String>>()
+            .join(", ");
+
+        write!(f, "{{{pairs}}}")
+    }
+}
+
+/// A user defined function, it captures the environment it was defined in so its body can see
+/// the bindings of the enclosing scopes
+#[derive(Clone)]
+pub struct Function {
+    pub parameters: Vec<Ident>,
+    pub body: BlockStatement,
+    pub env: Env,
+}
+
+impl Display for Function {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let parameters = self
+            .parameters
+            .iter()
+            .map(|p| p.to_string())
+            .collect::<Vec<String>>()
+            .join(", ");
+
+        write!(f, "fn({parameters}) {{ {} }}", self.body)
+    }
+}
+
+// The environment is not printed since a function can be stored in the environment it captures
+impl std::fmt::Debug for Function {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Function")
+            .field("parameters", &self.parameters)
+            .field("body", &self.body)
+            .finish_non_exhaustive()
+    }
+}
+
+// Two functions are equal only if they were created from the same literal in the same scope
+impl PartialEq for Function {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.env, &other.env)
+            && self.parameters == other.parameters
+            && self.body == other.body
+    }
+}
+
+/// A function compiled to bytecode, running it needs the constant pool it was compiled with
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CompiledFunction {
+    pub instructions: Instructions,
+    pub spans: SpanTable,
+    /// Slots for the parameters and the let statements of the body, the ones of its for loops
+    /// included
+    pub num_locals: usize,
+    pub num_parameters: usize,
+    /// Where the closures of the function take their free variables from
+    pub captures: Vec<Capture>,
+}
+
+/// Variable of the call creating a closure that the closure captures
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Capture {
+    Local(usize),
+    Free(usize),
+}
+
+impl Display for CompiledFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "compiled function ({} parameters, {} bytes)",
+            self.num_parameters,
+            self.instructions.len()
+        )
+    }
+}
+
+/// Variable of the vm, shared between the call that defines it and the closures that capture it,
+/// it is empty until it is set
+pub type Variable = Rc<RefCell<Option<Object>>>;
+
+/// A compiled function with the variables of the enclosing functions it refers to
+#[derive(Clone)]
+pub struct Closure {
+    pub function: Rc<CompiledFunction>,
+    pub free: Vec<Variable>,
+}
+
+// The variables are not printed since a closure can be stored in a variable it captures
+impl std::fmt::Debug for Closure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Closure")
+            .field("function", &self.function)
+            .finish_non_exhaustive()
+    }
+}
+
+// Two closures are equal only if they share their function and their variables
+impl PartialEq for Closure {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.function, &other.function)
+            && self.free.len() == other.free.len()
+            && self
+                .free
+                .iter()
+                .zip(&other.free)
+                .all(|(a, b)| Rc::ptr_eq(a, b))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn truthiness() {
+        assert!(Object::Integer(0).is_truthy());
+        assert!(Object::Boolean(true).is_truthy());
+        assert!(!Object::Boolean(false).is_truthy());
+        assert!(!Object::Null.is_truthy());
+    }
+
+    #[test]
+    fn hash_keys() {
+        let hello = Object::String("Hello".to_string());
+        assert_eq!(
+            hello.hash_key(),
+            Object::String("Hello".to_string()).hash_key()
+        );
+        assert_ne!(
+            hello.hash_key(),
+            Object::String("World".to_string()).hash_key()
+        );
+        assert_ne!(
+            Object::Integer(1).hash_key(),
+            Object::Boolean(true).hash_key()
+        );
+        assert_eq!(Object::Null.hash_key(), None);
+        assert_eq!(Object::Array(Rc::new(vec![])).hash_key(), None);
+    }
+
+    #[test]
+    fn hash_insertion_order() {
+        let mut hash = Hash::new();
+        hash.insert(HashKey::String("b".to_string()), Object::Integer(1));
+        hash.insert(HashKey::Integer(1), Object::Integer(2));
+        hash.insert(HashKey::String("b".to_string()), Object::Integer(3));
+
+        assert_eq!(hash.len(), 2);
+        assert_eq!(hash.to_string(), "{b: 3, 1: 2}");
+
+        let mut other = Hash::new();
+        other.insert(HashKey::Integer(1), Object::Integer(2));
+        other.insert(HashKey::String("b".to_string()), Object::Integer(3));
+        assert_eq!(hash, other);
+    }
+
+    #[test]
+    fn display() {
+        let cases = [
+            (Object::Integer(-5), "-5"),
+            (Object::Float(2.0), "2.0"),
+            (Object::Float(-0.25), "-0.25"),
+            (Object::Boolean(true), "true"),
+            (Object::String("a \"b\"".to_string()), "a \"b\""),
+            (Object::Null, "null"),
+            (
+                Object::Array(Rc::new(vec![Object::Integer(1), Object::Boolean(false)])),
+                "[1, false]",
+            ),
+            (Object::ReturnValue(Box::new(Object::Integer(1))), "1"),
+            (Object::Range(0, 3), "0..3"),
+            (Object::Error("boom".to_string()), "ERROR: boom"),
+        ];
+
+        for (obj, expected) in cases {
+            assert_eq!(obj.to_string(), expected);
+        }
+    }
+}
@@ -0,0 +1,314 @@
+use std::{fmt::Display, rc::Rc};
+
+use super::Object;
+
+/// Signature of the native functions, they receive the evaluated arguments of the call
+pub type NativeFn = dyn Fn(&[Object]) -> Result<Object, BuiltinError>;
+
+/// Number of arguments a builtin accepts
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Arity {
+    Exact(usize),
+    AtLeast(usize),
+    /// Between the two counts, both included, only the tests register builtins that need it
+    #[cfg(test)]
+    Range(usize, usize),
+}
+
+impl Arity {
+    fn accepts(&self, count: usize) -> bool {
+        match *self {
+            Arity::Exact(n) => count == n,
+            Arity::AtLeast(n) => count >= n,
+            #[cfg(test)]
+            Arity::Range(min, max) => (min..=max).contains(&count),
+        }
+    }
+}
+
+impl Display for Arity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Arity::Exact(n) => write!(f, "{n}"),
+            Arity::AtLeast(n) => write!(f, "{n} or more"),
+            #[cfg(test)]
+            Arity::Range(min, max) => write!(f, "{min} to {max}"),
+        }
+    }
+}
+
+/// Errors produced when calling a builtin
+#[derive(Debug, Clone, PartialEq)]
+pub enum BuiltinError {
+    WrongArgumentCount {
+        name: String,
+        want: Arity,
+        got: usize,
+    },
+    /// The argument at the given position (starting at 1) does not have one of the expected types
+    WrongArgumentType {
+        name: String,
+        position: usize,
+        want: &'static str,
+        got: &'static str,
+    },
+    Custom(String),
+}
+
+impl BuiltinError {
+    /// Builds the error reported when the argument at the given index has an unexpected type
+    pub fn wrong_type(name: &str, args: &[Object], index: usize, want: &'static str) -> Self {
+        BuiltinError::WrongArgumentType {
+            name: name.to_string(),
+            position: index + 1,
+            want,
+            got: args[index].type_name(),
+        }
+    }
+}
+
+impl Display for BuiltinError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BuiltinError::WrongArgumentCount { name, want, got } => write!(
+                f,
+                "wrong number of arguments to {name}: want={want}, got={got}"
+            ),
+            BuiltinError::WrongArgumentType {
+                name,
+                position,
+                want,
+                got,
+            } => write!(f, "argument {position} to {name} must be {want}, got {got}"),
+            BuiltinError::Custom(message) => write!(f, "{message}"),
+        }
+    }
+}
+
+impl std::error::Error for BuiltinError {}
+
+/// A native function callable from Monkey code
+#[derive(Clone)]
+pub struct Builtin {
+    pub name: String,
+    pub arity: Arity,
+    func: Rc<NativeFn>,
+}
+
+impl Builtin {
+    /// Calls the native function after checking the number of arguments
+    pub fn call(&self, args: &[Object]) -> Result<Object, BuiltinError> {
+        if !self.arity.accepts(args.len()) {
+            return Err(BuiltinError::WrongArgumentCount {
+                name: self.name.clone(),
+                want: self.arity,
+                got: args.len(),
+            });
+        }
+
+        (self.func)(args)
+    }
+}
+
+impl Display for Builtin {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "builtin function {}", self.name)
+    }
+}
+
+impl std::fmt::Debug for Builtin {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Builtin")
+            .field("name", &self.name)
+            .field("arity", &self.arity)
+            .finish_non_exhaustive()
+    }
+}
+
+impl PartialEq for Builtin {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name && Rc::ptr_eq(&self.func, &other.func)
+    }
+}
+
+/// Registry of the builtins available to a program, it comes with the standard library and
+/// embedding code can register its own native functions
+#[derive(Debug, Clone)]
+pub struct Builtins {
+    functions: Vec<Builtin>,
+}
+
+impl Default for Builtins {
+    fn default() -> Self {
+        let mut builtins = Self::empty();
+
+        builtins.register("len", Arity::Exact(1), |args| match &args[0] {
+            Object::String(s) => Ok(Object::Integer(s.chars().count() as i64)),
+            Object::Array(elements) => Ok(Object::Integer(elements.len() as i64)),
+            Object::Hash(hash) => Ok(Object::Integer(hash.len() as i64)),
+            _ => Err(BuiltinError::wrong_type(
+                "len",
+                args,
+                0,
+                "STRING, ARRAY or HASH",
+            )),
+        });
+        builtins.register("first", Arity::Exact(1), |args| match &args[0] {
+            Object::Array(elements) => Ok(elements.first().cloned().unwrap_or(Object::Null)),
+            _ => Err(BuiltinError::wrong_type("first", args, 0, "ARRAY")),
+        });
+        builtins.register("last", Arity::Exact(1), |args| match &args[0] {
+            Object::Array(elements) => Ok(elements.last().cloned().unwrap_or(Object::Null)),
+            _ => Err(BuiltinError::wrong_type("last", args, 0, "ARRAY")),
+        });
+        builtins.register("rest", Arity::Exact(1), |args| match &args[0] {
+            Object::Array(elements) if elements.is_empty() => Ok(Object::Null),
+            Object::Array(elements) => Ok(Object::Array(Rc::new(elements[1..].to_vec()))),
+            _ => Err(BuiltinError::wrong_type("rest", args, 0, "ARRAY")),
+        });
+        builtins.register("push", Arity::Exact(2), |args| match &args[0] {
+            Object::Array(elements) => {
+                let mut elements = elements.as_ref().clone();
+                elements.push(args[1].clone());
+                Ok(Object::Array(Rc::new(elements)))
+            }
+            _ => Err(BuiltinError::wrong_type("push", args, 0, "ARRAY")),
+        });
+        builtins.register("puts", Arity::AtLeast(0), |args| {
+            for arg in args {
+                println!("{arg}");
+            }
+            Ok(Object::Null)
+        });
+        builtins.register("type", Arity::Exact(1), |args| {
+            Ok(Object::String(args[0].type_name().to_string()))
+        });
+        builtins.register("str", Arity::Exact(1), |args| {
+            Ok(Object::String(args[0].to_string()))
+        });
+        builtins.register("int", Arity::Exact(1), |args| match &args[0] {
+            Object::Integer(value) => Ok(Object::Integer(*value)),
+            // Floats are truncated towards zero, out of range values saturate
+            Object::Float(value) => Ok(Object::Integer(*value as i64)),
+            Object::Boolean(value) => Ok(Object::Integer(*value as i64)),
+            Object::String(s) => match s.trim().parse() {
+                Ok(value) => Ok(Object::Integer(value)),
+                Err(_) => Err(BuiltinError::Custom(format!(
+                    "could not convert {s:?} to INTEGER"
+                ))),
+            },
+            _ => Err(BuiltinError::wrong_type(
+                "int",
+                args,
+                0,
+                "INTEGER, FLOAT, BOOLEAN or STRING",
+            )),
+        });
+        builtins.register("float", Arity::Exact(1), |args| match &args[0] {
+            Object::Integer(value) => Ok(Object::Float(*value as f64)),
+            Object::Float(value) => Ok(Object::Float(*value)),
+            Object::String(s) => match s.trim().parse() {
+                Ok(value) => Ok(Object::Float(value)),
+                Err(_) => Err(BuiltinError::Custom(format!(
+                    "could not convert {s:?} to FLOAT"
+                ))),
+            },
+            _ => Err(BuiltinError::wrong_type(
+                "float",
+                args,
+                0,
+                "INTEGER, FLOAT or STRING",
+            )),
+        });
+
+        builtins
+    }
+}
+
+impl Builtins {
+    /// Creates a registry without any builtin
+    pub fn empty() -> Self {
+        Self { functions: vec![] }
+    }
+
+    /// Registers a native function, a builtin with the same name is replaced
+    pub fn register<F>(&mut self, name: &str, arity: Arity, func: F)
+    where
+        F: Fn(&[Object]) -> Result<Object, BuiltinError> + 'static,
+    {
+        let builtin = Builtin {
+            name: name.to_string(),
+            arity,
+            func: Rc::new(func),
+        };
+
+        match self.functions.iter_mut().find(|b| b.name == name) {
+            Some(existing) => *existing = builtin,
+            None => self.functions.push(builtin),
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Builtin> {
+        self.functions.iter().find(|b| b.name == name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn arity_checks() {
+        let builtins = Builtins::default();
+        let len = builtins.get("len").unwrap();
+
+        assert_eq!(
+            len.call(&[]),
+            Err(BuiltinError::WrongArgumentCount {
+                name: "len".to_string(),
+                want: Arity::Exact(1),
+                got: 0
+            })
+        );
+        assert_eq!(
+            len.call(&[Object::Integer(1)]).unwrap_err().to_string(),
+            "argument 1 to len must be STRING, ARRAY or HASH, got INTEGER"
+        );
+    }
+
+    #[test]
+    fn register_native_functions() {
+        let mut builtins = Builtins::empty();
+        builtins.register("add", Arity::Range(1, 2), |args| {
+            let mut sum = 0;
+            for (i, arg) in args.iter().enumerate() {
+                match arg {
+                    Object::Integer(value) => sum += value,
+                    _ => return Err(BuiltinError::wrong_type("add", args, i, "INTEGER")),
+                }
+            }
+            Ok(Object::Integer(sum))
+        });
+
+        let add = builtins.get("add").unwrap();
+        assert_eq!(
+            add.call(&[Object::Integer(1), Object::Integer(2)]),
+            Ok(Object::Integer(3))
+        );
+        assert_eq!(
+            add.call(&[]).unwrap_err().to_string(),
+            "wrong number of arguments to add: want=1 to 2, got=0"
+        );
+        assert_eq!(
+            add.call(&[Object::Integer(1), Object::Null])
+                .unwrap_err()
+                .to_string(),
+            "argument 2 to add must be INTEGER, got NULL"
+        );
+
+        // Registering the same name replaces the previous builtin
+        builtins.register("add", Arity::Exact(0), |_| Ok(Object::Null));
+        assert_eq!(builtins.functions.len(), 1);
+        assert_eq!(builtins.get("add").unwrap().call(&[]), Ok(Object::Null));
+    }
+}
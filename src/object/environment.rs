@@ -0,0 +1,151 @@
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+use super::{Object, builtins::Builtins};
+
+/// Shared handle to an environment, scopes are shared between the code that creates them and the
+/// functions that capture them
+pub type Env = Rc<RefCell<Environment>>;
+
+/// Stores the bindings of a scope, lookups that miss the scope are forwarded to the enclosing one
+#[derive(Debug)]
+pub struct Environment {
+    store: HashMap<String, Object>,
+    outer: Option<Env>,
+    /// Only the outermost scope holds the builtins
+    builtins: Option<Rc<Builtins>>,
+    /// Number of function calls the scope is in
+    depth: usize,
+}
+
+impl Default for Environment {
+    fn default() -> Self {
+        Self::with_builtins(Builtins::default())
+    }
+}
+
+impl Environment {
+    /// Creates an outermost scope with the standard library builtins
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates an outermost scope with the given builtins
+    pub fn with_builtins(builtins: Builtins) -> Self {
+        Self {
+            store: HashMap::new(),
+            outer: None,
+            builtins: Some(Rc::new(builtins)),
+            depth: 0,
+        }
+    }
+
+    /// Creates a new scope whose lookups fall back to the given outer scope
+    pub fn new_enclosed(outer: Env) -> Self {
+        let depth = outer.borrow().depth;
+        Self {
+            store: HashMap::new(),
+            outer: Some(outer),
+            builtins: None,
+            depth,
+        }
+    }
+
+    /// Creates the scope of a function call, its lookups fall back to the scope the function was
+    /// defined in and it is one call deeper than the scope of the caller
+    pub fn new_call(outer: Env, caller: &Env) -> Self {
+        Self {
+            depth: caller.borrow().depth + 1,
+            ..Self::new_enclosed(outer)
+        }
+    }
+
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// Wraps the environment in a shared handle
+    pub fn into_env(self) -> Env {
+        Rc::new(RefCell::new(self))
+    }
+
+    /// Looks up a binding from the innermost to the outermost scope
+    pub fn get(&self, name: &str) -> Option<Object> {
+        match self.store.get(name) {
+            Some(obj) => Some(obj.clone()),
+            None => self.outer.as_ref()?.borrow().get(name),
+        }
+    }
+
+    /// Looks up a builtin from the outermost scope
+    pub fn builtin(&self, name: &str) -> Option<Object> {
+        match &self.outer {
+            Some(outer) => outer.borrow().builtin(name),
+            None => self
+                .builtins
+                .as_ref()?
+                .get(name)
+                .map(|b| Object::Builtin(b.clone())),
+        }
+    }
+
+    /// Binds the name in the current scope, shadowing any binding of an enclosing scope
+    pub fn set(&mut self, name: &str, value: Object) {
+        self.store.insert(name.to_string(), value);
+    }
+
+    /// Replaces the value of an existing binding in the innermost scope that has it, returns
+    /// false if no scope binds the name
+    pub fn assign(&mut self, name: &str, value: Object) -> bool {
+        match self.store.get_mut(name) {
+            Some(slot) => {
+                *slot = value;
+                true
+            }
+            None => match &self.outer {
+                Some(outer) => outer.borrow_mut().assign(name, value),
+                None => false,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn enclosed_lookup() {
+        let outer = Environment::new().into_env();
+        outer.borrow_mut().set("a", Object::Integer(1));
+        outer.borrow_mut().set("b", Object::Integer(2));
+
+        let mut inner = Environment::new_enclosed(outer.clone());
+        inner.set("b", Object::Boolean(true));
+
+        // The inner scope sees the outer bindings and shadows them without modifying them
+        assert_eq!(inner.get("a"), Some(Object::Integer(1)));
+        assert_eq!(inner.get("b"), Some(Object::Boolean(true)));
+        assert_eq!(outer.borrow().get("b"), Some(Object::Integer(2)));
+        assert_eq!(inner.get("c"), None);
+    }
+
+    #[test]
+    fn assign_nearest_binding() {
+        let outer = Environment::new().into_env();
+        outer.borrow_mut().set("a", Object::Integer(1));
+        outer.borrow_mut().set("b", Object::Integer(2));
+
+        let mut inner = Environment::new_enclosed(outer.clone());
+        inner.set("b", Object::Integer(3));
+
+        assert!(inner.assign("a", Object::Integer(10)));
+        assert!(inner.assign("b", Object::Integer(30)));
+        assert!(!inner.assign("c", Object::Null));
+
+        assert_eq!(outer.borrow().get("a"), Some(Object::Integer(10)));
+        // The shadowed binding is left alone
+        assert_eq!(outer.borrow().get("b"), Some(Object::Integer(2)));
+        assert_eq!(inner.get("b"), Some(Object::Integer(30)));
+        assert_eq!(inner.get("c"), None);
+    }
+}
@@ -1,11 +1,14 @@
 pub mod ast;
+pub mod error;
 mod parser_test;
 pub mod precedence;
 
 use ast::{
-    BlockStatement, Boolean, Expression, Ident, IfExp, InfixExp, Integer, Let, Literal, PrefixExp,
-    Program, Return, Statement,
+    ArrayLiteral, AssignExp, BlockStatement, Boolean, CallExp, Expression, Float, For,
+    FunctionLiteral, HashLiteral, Ident, IfExp, IndexExp, InfixExp, Integer, Let, Literal,
+    PrefixExp, Program, RangeExp, Return, SliceExp, Statement, StringLiteral, While,
 };
+use error::ParseError;
 use precedence::{Precedence, get_token_precedence};
 
 use crate::lexer::{
@@ -13,8 +16,11 @@ use crate::lexer::{
     token::{Span, Token, TokenKind},
 };
 
-type ParseError = String;
-type ParserErrors = Vec<ParseError>;
+pub type ParserErrors = Vec<ParseError>;
+
+/// Maximum number of expressions and blocks nested inside each other, deeper programs would
+/// overflow the native stack of the parser and of the passes that walk the tree after it
+pub const MAX_NESTING: usize = 256;
 
 pub struct Parser<'s> {
     lexer: Lexer<'s>,
@@ -22,6 +28,9 @@ pub struct Parser<'s> {
     current_token: Token,
     peek_token: Token,
 
+    /// Number of expressions and blocks enclosing the one being parsed
+    depth: usize,
+
     pub errors: ParserErrors,
 }
 
@@ -34,6 +43,7 @@ impl<'s> Parser<'s> {
             lexer,
             current_token,
             peek_token,
+            depth: 0,
             errors: vec![],
         }
     }
@@ -51,11 +61,16 @@ impl<'s> Parser<'s> {
             self.bump();
             Ok(())
         } else {
-            // If it did no tmatch return false and no nothing
-            Err(format!(
-                "Expected next token to be: {} got: {}",
-                kind, self.peek_token.kind
-            ))
+            // If it did no tmatch return an error pointing at the peeked token
+            Err(self.peek_error(std::slice::from_ref(kind)))
+        }
+    }
+
+    /// Builds the error for a peeked token that is none of the expected ones
+    fn peek_error(&self, expected: &[TokenKind]) -> ParseError {
+        ParseError::UnexpectedToken {
+            expected: expected.to_vec(),
+            found: self.peek_token.clone(),
         }
     }
 
@@ -71,22 +86,80 @@ impl<'s> Parser<'s> {
 
     /// Entry Point of the parser, starts parsing the lexer tokens and builds a program
     pub fn parse_program(&mut self) -> Result<Program, ParserErrors> {
+        let (prog, errors) = self.parse_program_partial();
+
+        if errors.is_empty() {
+            Ok(prog)
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Parses the whole program even if it has errors, the statements that could not be parsed
+    /// are replaced by error nodes so the rest of the program can still be inspected
+    pub fn parse_program_partial(&mut self) -> (Program, ParserErrors) {
         let mut prog = Program::new();
         // Wile not finding the EOF we keep looping
         while !self.current_token_is(&TokenKind::Eof) {
-            match self.parse_statement() {
-                Ok(s) => prog.statements.push(s),
-                Err(e) => self.errors.push(e),
-            }
+            let stmt = self.parse_statement_or_recover();
+            prog.statements.push(stmt);
 
-            // Bump the internal tokens
+            // Bump the internal tokens, a stray } is skipped too since there is no block to close
             self.bump();
         }
 
-        if self.errors.is_empty() {
-            Ok(prog)
-        } else {
-            Err(self.errors.clone())
+        (prog, self.errors.clone())
+    }
+
+    /// Parses a statement, if it fails the error is stored and the tokens are skipped until the
+    /// end of the statement, which is then replaced by an error node
+    fn parse_statement_or_recover(&mut self) -> Statement {
+        let start = self.current_token.span.start;
+
+        match self.parse_statement() {
+            Ok(s) => s,
+            Err(e) => {
+                self.errors.push(e);
+                self.synchronize();
+
+                let end = self.current_token.span.end.max(start);
+                Statement::Error(Span { start, end })
+            }
+        }
+    }
+
+    /// Skips tokens until the end of the current statement, it stops on a ; or before a token
+    /// that starts or closes a statement, braces opened meanwhile are skipped with their content
+    fn synchronize(&mut self) {
+        let mut depth = 0;
+
+        loop {
+            match self.current_token.kind {
+                TokenKind::Eof => return,
+                TokenKind::LeftBrace => depth += 1,
+                // The brace closes the enclosing block, leave it to the block
+                TokenKind::RightBrace if depth == 0 => return,
+                TokenKind::RightBrace => depth -= 1,
+                TokenKind::SemiColon if depth == 0 => return,
+                _ => {}
+            }
+
+            let at_boundary = matches!(
+                self.peek_token.kind,
+                TokenKind::Let
+                    | TokenKind::Return
+                    | TokenKind::While
+                    | TokenKind::For
+                    | TokenKind::Break
+                    | TokenKind::Continue
+                    | TokenKind::RightBrace
+                    | TokenKind::Eof
+            );
+            if depth == 0 && at_boundary {
+                return;
+            }
+
+            self.bump();
         }
     }
 
@@ -95,6 +168,9 @@ impl<'s> Parser<'s> {
         let st = match self.current_token.kind {
             TokenKind::Let => self.parse_let_statement()?,
             TokenKind::Return => self.parse_return_statement()?,
+            TokenKind::While => self.parse_while_statement()?,
+            TokenKind::For => self.parse_for_statement()?,
+            TokenKind::Break | TokenKind::Continue => self.parse_loop_control()?,
             _ => self.parse_expression_statement()?,
         };
 
@@ -104,7 +180,10 @@ impl<'s> Parser<'s> {
     /// Parses a LET statement and returns it
     fn parse_let_statement(&mut self) -> Result<Statement, ParseError> {
         if !self.current_token_is(&TokenKind::Let) {
-            return Err(format!("Expected LET found: {}", self.current_token.kind));
+            return Err(ParseError::UnexpectedToken {
+                expected: vec![TokenKind::Let],
+                found: self.current_token.clone(),
+            });
         }
 
         // get the current span start
@@ -113,19 +192,20 @@ impl<'s> Parser<'s> {
         self.bump();
 
         let ident = self.current_token.clone();
-        // let mut _ident_name = ParseError::new();
         match &self.current_token.kind {
-            TokenKind::Ident { .. } => {
-                // _ident_name = name.clone();
-            }
-            _ => return Err(format!("Not an identifier {}", self.current_token.kind)),
+            TokenKind::Ident { .. } => {}
+            _ => return Err(ParseError::ExpectedIdentifier { found: ident }),
         }
 
         self.expect_peek(&TokenKind::Assign)?;
 
-        //TODO: Parse expressions later on
-        while !self.current_token_is(&TokenKind::SemiColon) {
-            // Otherwise advance the cursors
+        // Skip the = to be on top of the expression
+        self.bump();
+
+        let exp = self.parse_expression(Precedence::Lowset)?;
+
+        // The semicolon is optional
+        if self.peek_token_is(&TokenKind::SemiColon) {
             self.bump();
         }
 
@@ -134,7 +214,7 @@ impl<'s> Parser<'s> {
 
         Ok(Statement::Let(Let {
             identifier: ident,
-            exp: None,
+            exp,
             span: Span { start, end },
         }))
     }
@@ -142,27 +222,122 @@ impl<'s> Parser<'s> {
     /// Parses a return statement
     fn parse_return_statement(&mut self) -> Result<Statement, ParseError> {
         if !self.current_token_is(&TokenKind::Return) {
-            return Err(format!(
-                "Expected RETURN found: {}",
-                self.current_token.kind
-            ));
+            return Err(ParseError::UnexpectedToken {
+                expected: vec![TokenKind::Return],
+                found: self.current_token.clone(),
+            });
         }
 
         let start = self.current_token.span.start;
 
-        //TODO: Here we parse the expression so in the mean time
-        while !self.current_token_is(&TokenKind::SemiColon) {
-            // Otherwise advance the cursors
+        // A bare return has no value
+        let exp = if self.peek_token_is(&TokenKind::SemiColon)
+            || self.peek_token_is(&TokenKind::RightBrace)
+            || self.peek_token_is(&TokenKind::Eof)
+        {
+            None
+        } else {
+            // Skip the return to be on top of the expression
+            self.bump();
+            Some(self.parse_expression(Precedence::Lowset)?)
+        };
+
+        // The semicolon is optional
+        if self.peek_token_is(&TokenKind::SemiColon) {
             self.bump();
         }
 
         let end = self.current_token.span.end;
         Ok(Statement::Return(Return {
-            exp: None,
+            exp,
+            span: Span { start, end },
+        }))
+    }
+
+    /// Parses a while loop, the current token must be the while and it will end on the } of the
+    /// body
+    fn parse_while_statement(&mut self) -> Result<Statement, ParseError> {
+        let start = self.current_token.span.start;
+
+        self.expect_peek(&TokenKind::LeftParen)?;
+        // Skip the ( to be on top of the condition
+        self.bump();
+
+        let cond = self.parse_expression(Precedence::Lowset)?;
+
+        self.expect_peek(&TokenKind::RightParen)?;
+        self.expect_peek(&TokenKind::LeftBrace)?;
+
+        let body = self.parse_block_statement()?;
+
+        // The semicolon is optional
+        if self.peek_token_is(&TokenKind::SemiColon) {
+            self.bump();
+        }
+
+        let end = self.current_token.span.end;
+        Ok(Statement::While(While {
+            cond,
+            body,
+            span: Span { start, end },
+        }))
+    }
+
+    /// Parses a for loop like `for (x in iterable) { }`, the current token must be the for and it
+    /// will end on the } of the body
+    fn parse_for_statement(&mut self) -> Result<Statement, ParseError> {
+        let start = self.current_token.span.start;
+
+        self.expect_peek(&TokenKind::LeftParen)?;
+        self.bump();
+        let variable = self.parse_parameter()?;
+
+        self.expect_peek(&TokenKind::In)?;
+        // Skip the in to be on top of the iterable
+        self.bump();
+
+        let iterable = self.parse_expression(Precedence::Lowset)?;
+
+        self.expect_peek(&TokenKind::RightParen)?;
+        self.expect_peek(&TokenKind::LeftBrace)?;
+
+        let body = self.parse_block_statement()?;
+
+        // The semicolon is optional
+        if self.peek_token_is(&TokenKind::SemiColon) {
+            self.bump();
+        }
+
+        let end = self.current_token.span.end;
+        Ok(Statement::For(For {
+            variable,
+            iterable,
+            body,
             span: Span { start, end },
         }))
     }
 
+    /// Parses a break or a continue, whether it is inside a loop is checked when running it
+    fn parse_loop_control(&mut self) -> Result<Statement, ParseError> {
+        let start = self.current_token.span.start;
+        let is_break = self.current_token_is(&TokenKind::Break);
+
+        // The semicolon is optional
+        if self.peek_token_is(&TokenKind::SemiColon) {
+            self.bump();
+        }
+
+        let span = Span {
+            start,
+            end: self.current_token.span.end,
+        };
+        if is_break {
+            Ok(Statement::Break(span))
+        } else {
+            Ok(Statement::Continue(span))
+        }
+    }
+
     fn parse_expression_statement(&mut self) -> Result<Statement, ParseError> {
         let exp = self.parse_expression(Precedence::Lowset)?;
         if self.peek_token_is(&TokenKind::SemiColon) {
@@ -172,6 +347,29 @@ impl<'s> Parser<'s> {
     }
 
     fn parse_expression(&mut self, precedence: Precedence) -> Result<Expression, ParseError> {
+        let depth = self.depth;
+        let exp = self.parse_nested_expression(precedence);
+        self.depth = depth;
+        exp
+    }
+
+    /// Counts one more level of nesting, returns false once there are too many of them
+    fn nest(&mut self) -> bool {
+        self.depth += 1;
+        self.depth <= MAX_NESTING
+    }
+
+    /// Parses an expression for parse_expression, which restores the nesting depth afterwards
+    fn parse_nested_expression(
+        &mut self,
+        precedence: Precedence,
+    ) -> Result<Expression, ParseError> {
+        if !self.nest() {
+            return Err(ParseError::TooDeeplyNested {
+                found: self.current_token.clone(),
+            });
+        }
+
         let left_start = self.current_token.span.start;
         let mut left_exp = self.parse_prefix_expression()?;
 
@@ -182,8 +380,14 @@ impl<'s> Parser<'s> {
             let infix = self.parse_infix_expression(&left_exp, left_start);
             match infix {
                 Some(infix) => {
-                    // Now the left part becomes the infix for the next iteration
+                    // Now the left part becomes the infix for the next iteration, one level
+                    // deeper than before
                     left_exp = infix?;
+                    if !self.nest() {
+                        return Err(ParseError::TooDeeplyNested {
+                            found: self.current_token.clone(),
+                        });
+                    }
                 }
                 None => {
                     return Ok(left_exp);
@@ -206,7 +410,15 @@ impl<'s> Parser<'s> {
                 value: *value,
                 span,
             }))),
-            TokenKind::Bang | TokenKind::Minus => {
+            TokenKind::Float(value) => Ok(Expression::Literal(Literal::Float(Float {
+                value: *value,
+                span,
+            }))),
+            TokenKind::String(value) => Ok(Expression::Literal(Literal::String(StringLiteral {
+                value: value.clone(),
+                span,
+            }))),
+            TokenKind::Bang | TokenKind::Minus | TokenKind::Tilde => {
                 let operator = self.current_token.clone();
 
                 self.bump();
@@ -242,10 +454,28 @@ impl<'s> Parser<'s> {
                 Ok(exp)
             }
             TokenKind::If => self.parse_if_expression(),
-            _ => Err(format!(
-                "Prefix parse expression not implemented for {}",
-                self.current_token.kind
-            )),
+            TokenKind::Fn => self.parse_function_literal(),
+            // Block statements are only parsed after the tokens that open them, so a brace in
+            // prefix position is always a hash
+            TokenKind::LeftBrace => self.parse_hash_literal(),
+            TokenKind::LeftBracket => {
+                let elements = self.parse_expression_list(&TokenKind::RightBracket)?;
+
+                let end = self.current_token.span.end;
+                Ok(Expression::Array(ArrayLiteral {
+                    elements,
+                    span: Span {
+                        start: span.start,
+                        end,
+                    },
+                }))
+            }
+            TokenKind::Illegal(_) => Err(ParseError::IllegalToken {
+                found: self.current_token.clone(),
+            }),
+            _ => Err(ParseError::ExpectedExpression {
+                found: self.current_token.clone(),
+            }),
         }
     }
 
@@ -261,10 +491,20 @@ impl<'s> Parser<'s> {
             | TokenKind::NotEq
             | TokenKind::LT
             | TokenKind::GT
+            | TokenKind::LTEq
+            | TokenKind::GTEq
             | TokenKind::Plus
             | TokenKind::Minus
             | TokenKind::Star
-            | TokenKind::Slash => {
+            | TokenKind::Slash
+            | TokenKind::Percent
+            | TokenKind::And
+            | TokenKind::Or
+            | TokenKind::BitAnd
+            | TokenKind::BitOr
+            | TokenKind::BitXor
+            | TokenKind::ShiftLeft
+            | TokenKind::ShiftRight => {
                 // Advance the cursors to be on top of the token
                 self.bump();
 
@@ -296,10 +536,270 @@ impl<'s> Parser<'s> {
                     },
                 })))
             }
+            TokenKind::Assign
+            | TokenKind::PlusAssign
+            | TokenKind::MinusAssign
+            | TokenKind::StarAssign
+            | TokenKind::SlashAssign
+            | TokenKind::PercentAssign => {
+                self.bump();
+                let operator = self.current_token.clone();
+
+                if !is_assignable(left) {
+                    return Some(Err(ParseError::InvalidAssignmentTarget { found: operator }));
+                }
+
+                self.bump();
+                // Parsed with the lowest precedence so assignments chain from the right,
+                // a = b = 1 is a = (b = 1)
+                let value = match self.parse_expression(Precedence::Lowset) {
+                    Err(e) => return Some(Err(e)),
+                    Ok(exp) => exp,
+                };
+
+                Some(Ok(Expression::Assign(AssignExp {
+                    target: Box::new(left.clone()),
+                    operator,
+                    value: Box::new(value),
+                    span: Span {
+                        start: left_start,
+                        end: self.current_token.span.end,
+                    },
+                })))
+            }
+            TokenKind::DotDot => {
+                self.bump();
+                // Skip the .. to be on top of the end
+                self.bump();
+
+                let end = match self.parse_expression(Precedence::Range) {
+                    Err(e) => return Some(Err(e)),
+                    Ok(exp) => exp,
+                };
+
+                Some(Ok(Expression::Range(RangeExp {
+                    start: Box::new(left.clone()),
+                    end: Box::new(end),
+                    span: Span {
+                        start: left_start,
+                        end: self.current_token.span.end,
+                    },
+                })))
+            }
+            TokenKind::LeftParen => {
+                // Advance the cursors to be on top of the (
+                self.bump();
+
+                let arguments = match self.parse_expression_list(&TokenKind::RightParen) {
+                    Err(e) => return Some(Err(e)),
+                    Ok(args) => args,
+                };
+
+                let end = self.current_token.span.end;
+
+                Some(Ok(Expression::Call(CallExp {
+                    function: Box::new(left.clone()),
+                    arguments,
+                    span: Span {
+                        start: left_start,
+                        end,
+                    },
+                })))
+            }
+            TokenKind::LeftBracket => {
+                // Advance the cursors to be on top of the [
+                self.bump();
+                Some(self.parse_index_expression(left, left_start))
+            }
             _ => None,
         }
     }
 
+    /// Parses an index `left[index]` or a slice `left[start:end]` expression, the current index
+    /// must be at the [ and it will end on the matching ]
+    fn parse_index_expression(
+        &mut self,
+        left: &Expression,
+        left_start: usize,
+    ) -> Result<Expression, ParseError> {
+        // The start of a slice can be omitted
+        let start = if self.peek_token_is(&TokenKind::Colon) {
+            None
+        } else {
+            self.bump();
+            Some(Box::new(self.parse_expression(Precedence::Lowset)?))
+        };
+
+        if !self.peek_token_is(&TokenKind::Colon) {
+            self.expect_peek(&TokenKind::RightBracket)?;
+
+            let end = self.current_token.span.end;
+            return Ok(Expression::Index(IndexExp {
+                left: Box::new(left.clone()),
+                // Safe since the start can only be omitted when followed by a colon
+                index: start.expect("index expression without index"),
+                span: Span {
+                    start: left_start,
+                    end,
+                },
+            }));
+        }
+
+        // Bump to the :
+        self.bump();
+
+        // The end of a slice can be omitted too
+        let end_exp = if self.peek_token_is(&TokenKind::RightBracket) {
+            None
+        } else {
+            self.bump();
+            Some(Box::new(self.parse_expression(Precedence::Lowset)?))
+        };
+
+        self.expect_peek(&TokenKind::RightBracket)?;
+
+        let end = self.current_token.span.end;
+        Ok(Expression::Slice(SliceExp {
+            left: Box::new(left.clone()),
+            start,
+            end: end_exp,
+            span: Span {
+                start: left_start,
+                end,
+            },
+        }))
+    }
+
+    /// Parses a comma separated list of expressions, the current index must be at the opening
+    /// token and it will end on the given closing token
+    fn parse_expression_list(&mut self, end: &TokenKind) -> Result<Vec<Expression>, ParseError> {
+        let mut list = vec![];
+
+        // Empty list
+        if self.peek_token_is(end) {
+            self.bump();
+            return Ok(list);
+        }
+
+        // Bump the opening token
+        self.bump();
+        list.push(self.parse_expression(Precedence::Lowset)?);
+
+        while self.peek_token_is(&TokenKind::Comma) {
+            // Bump the previous expression and the comma
+            self.bump();
+            self.bump();
+            list.push(self.parse_expression(Precedence::Lowset)?);
+        }
+
+        if !self.peek_token_is(end) {
+            return Err(self.peek_error(&[TokenKind::Comma, end.clone()]));
+        }
+        self.bump();
+
+        Ok(list)
+    }
+
+    /// Parses a hash literal, the current index must be at a { and it will end on the matching }
+    fn parse_hash_literal(&mut self) -> Result<Expression, ParseError> {
+        let start = self.current_token.span.start;
+        let mut pairs = vec![];
+
+        while !self.peek_token_is(&TokenKind::RightBrace) {
+            // Bump the { or the previous comma
+            self.bump();
+            let key = self.parse_expression(Precedence::Lowset)?;
+
+            self.expect_peek(&TokenKind::Colon)?;
+
+            // Bump the :
+            self.bump();
+            let value = self.parse_expression(Precedence::Lowset)?;
+
+            pairs.push((key, value));
+
+            // Pairs are separated by commas, a trailing one is allowed
+            if !self.peek_token_is(&TokenKind::RightBrace) {
+                if !self.peek_token_is(&TokenKind::Comma) {
+                    return Err(self.peek_error(&[TokenKind::Comma, TokenKind::RightBrace]));
+                }
+                self.bump();
+            }
+        }
+
+        self.expect_peek(&TokenKind::RightBrace)?;
+
+        let end = self.current_token.span.end;
+        Ok(Expression::Hash(HashLiteral {
+            pairs,
+            span: Span { start, end },
+        }))
+    }
+
+    /// Parses a function literal, the current index must be at a FN
+    fn parse_function_literal(&mut self) -> Result<Expression, ParseError> {
+        let start = self.current_token.span.start;
+
+        self.expect_peek(&TokenKind::LeftParen)?;
+
+        let parameters = self.parse_function_parameters()?;
+
+        self.expect_peek(&TokenKind::LeftBrace)?;
+
+        let body = self.parse_block_statement()?;
+
+        let end = self.current_token.span.end;
+
+        Ok(Expression::Function(FunctionLiteral {
+            parameters,
+            body,
+            span: Span { start, end },
+        }))
+    }
+
+    /// Parses the parameters of a function, the current index must be at a ( and it will end on
+    /// the matching )
+    fn parse_function_parameters(&mut self) -> Result<Vec<Ident>, ParseError> {
+        let mut parameters = vec![];
+
+        // No parameters
+        if self.peek_token_is(&TokenKind::RightParen) {
+            self.bump();
+            return Ok(parameters);
+        }
+
+        // Bump the (
+        self.bump();
+        parameters.push(self.parse_parameter()?);
+
+        while self.peek_token_is(&TokenKind::Comma) {
+            // Bump the previous parameter and the comma
+            self.bump();
+            self.bump();
+            parameters.push(self.parse_parameter()?);
+        }
+
+        if !self.peek_token_is(&TokenKind::RightParen) {
+            return Err(self.peek_error(&[TokenKind::Comma, TokenKind::RightParen]));
+        }
+        self.bump();
+
+        Ok(parameters)
+    }
+
+    /// Parses a single function parameter, which must be an identifier
+    fn parse_parameter(&self) -> Result<Ident, ParseError> {
+        match &self.current_token.kind {
+            TokenKind::Ident { name } => Ok(Ident {
+                name: name.clone(),
+                span: self.current_token.span.clone(),
+            }),
+            _ => Err(ParseError::ExpectedIdentifier {
+                found: self.current_token.clone(),
+            }),
+        }
+    }
+
     /// Parses a if expression, the current index must be at an IF
     fn parse_if_expression(&mut self) -> Result<Expression, ParseError> {
         let start = self.current_token.span.start;
@@ -335,7 +835,6 @@ impl<'s> Parser<'s> {
 
         // Get the current token
         let end = self.current_token.span.end;
-        println!("{}", self.current_token);
 
         Ok(Expression::If(IfExp {
             cond: Box::new(cond),
@@ -348,6 +847,20 @@ impl<'s> Parser<'s> {
     /// Parses a block statement, the current index must be at a { token and it will end on the
     /// matching }
     fn parse_block_statement(&mut self) -> Result<BlockStatement, ParseError> {
+        let depth = self.depth;
+        let block = self.parse_nested_block();
+        self.depth = depth;
+        block
+    }
+
+    /// Parses a block for parse_block_statement, which restores the nesting depth afterwards
+    fn parse_nested_block(&mut self) -> Result<BlockStatement, ParseError> {
+        if !self.nest() {
+            return Err(ParseError::TooDeeplyNested {
+                found: self.current_token.clone(),
+            });
+        }
+
         let start = self.current_token.span.start;
         // Bump the {
         self.bump();
@@ -357,14 +870,27 @@ impl<'s> Parser<'s> {
         while !self.current_token_is(&TokenKind::RightBrace)
             && !self.current_token_is(&TokenKind::Eof)
         {
-            if let Ok(s) = self.parse_statement() {
-                statements.push(s);
+            let stmt = self.parse_statement_or_recover();
+
+            // A failed statement can stop on the } closing the block, it must not be skipped
+            if matches!(stmt, Statement::Error(_)) && self.current_token_is(&TokenKind::RightBrace)
+            {
+                statements.push(stmt);
+                break;
             }
+            statements.push(stmt);
 
             // Here we bump since the statement leaves the current pointer to the last token parsed
             self.bump();
         }
 
+        if self.current_token_is(&TokenKind::Eof) {
+            return Err(ParseError::UnexpectedToken {
+                expected: vec![TokenKind::RightBrace],
+                found: self.current_token.clone(),
+            });
+        }
+
         let end = self.current_token.span.end;
 
         Ok(BlockStatement {
@@ -373,3 +899,12 @@ impl<'s> Parser<'s> {
         })
     }
 }
+
+/// Only variables and indexes into them can be assigned, like `a` or `a[0]["k"]`
+fn is_assignable(exp: &Expression) -> bool {
+    match exp {
+        Expression::Identifier(_) => true,
+        Expression::Index(index) => is_assignable(&index.left),
+        _ => false,
+    }
+}
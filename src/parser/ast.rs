@@ -49,6 +49,12 @@ pub enum Statement {
     Let(Let),
     Return(Return),
     Expression(Expression),
+    While(While),
+    For(For),
+    Break(Span),
+    Continue(Span),
+    /// Placeholder for a statement that could not be parsed
+    Error(Span),
 }
 
 impl Display for Statement {
@@ -58,12 +64,40 @@ impl Display for Statement {
                 identifier, exp, ..
             }) => {
                 if let TokenKind::Ident { name } = &identifier.kind {
-                    return write!(f, "let {name} = {exp:?};");
+                    return write!(f, "let {name} = {exp};");
                 }
                 unreachable!("Cannot have let without identifier")
             }
-            Statement::Return(Return { exp, .. }) => write!(f, "return {exp:?};"),
+            Statement::Return(Return { exp, .. }) => match exp {
+                Some(exp) => write!(f, "return {exp};"),
+                None => write!(f, "return;"),
+            },
             Statement::Expression(exp) => write!(f, "{exp}"),
+            Statement::While(While { cond, body, .. }) => write!(f, "while {cond} {{ {body} }}"),
+            Statement::For(For {
+                variable,
+                iterable,
+                body,
+                ..
+            }) => write!(f, "for {variable} in {iterable} {{ {body} }}"),
+            Statement::Break(_) => write!(f, "break;"),
+            Statement::Continue(_) => write!(f, "continue;"),
+            Statement::Error(_) => write!(f, "<error>"),
+        }
+    }
+}
+
+impl Statement {
+    pub fn span(&self) -> &Span {
+        match self {
+            Statement::Let(Let { span, .. })
+            | Statement::Return(Return { span, .. })
+            | Statement::While(While { span, .. })
+            | Statement::For(For { span, .. })
+            | Statement::Break(span)
+            | Statement::Continue(span)
+            | Statement::Error(span) => span,
+            Statement::Expression(exp) => exp.span(),
         }
     }
 }
@@ -71,7 +105,7 @@ impl Display for Statement {
 #[derive(Clone, Debug, PartialEq)]
 pub struct Let {
     pub identifier: Token,
-    pub exp: Option<Expression>,
+    pub exp: Expression,
     pub span: Span,
 }
 
@@ -81,6 +115,23 @@ pub struct Return {
     pub span: Span,
 }
 
+#[derive(Clone, Debug, PartialEq)]
+pub struct While {
+    pub cond: Expression,
+    pub body: BlockStatement,
+    pub span: Span,
+}
+
+/// Loop over the elements of an array, the integers of a range, the chars of a string or the
+/// keys of a hash
+#[derive(Clone, Debug, PartialEq)]
+pub struct For {
+    pub variable: Ident,
+    pub iterable: Expression,
+    pub body: BlockStatement,
+    pub span: Span,
+}
+
 #[derive(Clone, Debug, PartialEq)]
 pub enum Expression {
     Identifier(Ident),
@@ -88,6 +139,14 @@ pub enum Expression {
     Prefix(PrefixExp),
     Infix(InfixExp),
     If(IfExp),
+    Function(FunctionLiteral),
+    Call(CallExp),
+    Array(ArrayLiteral),
+    Index(IndexExp),
+    Slice(SliceExp),
+    Hash(HashLiteral),
+    Range(RangeExp),
+    Assign(AssignExp),
 }
 
 impl Display for Expression {
@@ -113,6 +172,61 @@ impl Display for Expression {
                 Some(block) => write!(f, "if {} {{ {} }} else {{ {} }}", cond, consequence, block),
                 None => write!(f, "if {} {{ {} }}", cond, consequence),
             },
+            Expression::Function(FunctionLiteral {
+                parameters, body, ..
+            }) => write!(f, "fn({}) {{ {} }}", join(parameters), body),
+            Expression::Call(CallExp {
+                function,
+                arguments,
+                ..
+            }) => write!(f, "{}({})", function, join(arguments)),
+            Expression::Array(ArrayLiteral { elements, .. }) => write!(f, "[{}]", join(elements)),
+            Expression::Index(IndexExp { left, index, .. }) => write!(f, "({}[{}])", left, index),
+            Expression::Slice(SliceExp {
+                left, start, end, ..
+            }) => {
+                let start = start.as_ref().map(|s| s.to_string()).unwrap_or_default();
+                let end = end.as_ref().map(|e| e.to_string()).unwrap_or_default();
+                write!(f, "({}[{}:{}])", left, start, end)
+            }
+            Expression::Hash(HashLiteral { pairs, .. }) => {
+                let pairs = pairs
+                    .iter()
+                    .map(|(key, value)| format!("{key}: {value}"))
+                    .collect::<Vec<String>>()
+                    .join(", ");
+                write!(f, "{{{pairs}}}")
+            }
+            Expression::Range(RangeExp { start, end, .. }) => write!(f, "({start}..{end})"),
+            Expression::Assign(AssignExp {
+                target,
+                operator,
+                value,
+                ..
+            }) => write!(f, "({target} {} {value})", operator.kind),
+        }
+    }
+}
+
+impl Expression {
+    pub fn span(&self) -> &Span {
+        match self {
+            Expression::Identifier(Ident { span, .. })
+            | Expression::Literal(Literal::Integer(Integer { span, .. }))
+            | Expression::Literal(Literal::Float(Float { span, .. }))
+            | Expression::Literal(Literal::Boolean(Boolean { span, .. }))
+            | Expression::Literal(Literal::String(StringLiteral { span, .. }))
+            | Expression::Prefix(PrefixExp { span, .. })
+            | Expression::Infix(InfixExp { span, .. })
+            | Expression::If(IfExp { span, .. })
+            | Expression::Function(FunctionLiteral { span, .. })
+            | Expression::Call(CallExp { span, .. })
+            | Expression::Array(ArrayLiteral { span, .. })
+            | Expression::Index(IndexExp { span, .. })
+            | Expression::Slice(SliceExp { span, .. })
+            | Expression::Hash(HashLiteral { span, .. })
+            | Expression::Range(RangeExp { span, .. })
+            | Expression::Assign(AssignExp { span, .. }) => span,
         }
     }
 }
@@ -132,14 +246,19 @@ impl Display for Ident {
 #[derive(Clone, Debug, PartialEq)]
 pub enum Literal {
     Integer(Integer),
+    Float(Float),
     Boolean(Boolean),
+    String(StringLiteral),
 }
 
 impl Display for Literal {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Literal::Integer(integer) => write!(f, "{}", integer.value),
+            Literal::Float(float) => write!(f, "{:?}", float.value),
             Literal::Boolean(boolean) => write!(f, "{}", boolean.value),
+            // Quoted and escaped so the output can be lexed back
+            Literal::String(string) => write!(f, "{:?}", string.value),
         }
     }
 }
@@ -150,12 +269,24 @@ pub struct Integer {
     pub span: Span,
 }
 
+#[derive(Clone, Debug, PartialEq)]
+pub struct Float {
+    pub value: f64,
+    pub span: Span,
+}
+
 #[derive(Clone, Debug, PartialEq)]
 pub struct Boolean {
     pub value: bool,
     pub span: Span,
 }
 
+#[derive(Clone, Debug, PartialEq)]
+pub struct StringLiteral {
+    pub value: String,
+    pub span: Span,
+}
+
 #[derive(Clone, Debug, PartialEq)]
 pub struct PrefixExp {
     pub operator: Token,
@@ -179,6 +310,67 @@ pub struct IfExp {
     pub span: Span,
 }
 
+#[derive(Clone, Debug, PartialEq)]
+pub struct FunctionLiteral {
+    pub parameters: Vec<Ident>,
+    pub body: BlockStatement,
+    pub span: Span,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct CallExp {
+    pub function: Box<Expression>,
+    pub arguments: Vec<Expression>,
+    pub span: Span,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ArrayLiteral {
+    pub elements: Vec<Expression>,
+    pub span: Span,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct IndexExp {
+    pub left: Box<Expression>,
+    pub index: Box<Expression>,
+    pub span: Span,
+}
+
+/// A range of a collection `left[start:end]`, both bounds are optional and default to the start
+/// and the end of the collection
+#[derive(Clone, Debug, PartialEq)]
+pub struct SliceExp {
+    pub left: Box<Expression>,
+    pub start: Option<Box<Expression>>,
+    pub end: Option<Box<Expression>>,
+    pub span: Span,
+}
+
+/// Assignment to an existing variable or to an element of an array or a hash, the operator is
+/// either `=` or a compound one like `+=`
+#[derive(Clone, Debug, PartialEq)]
+pub struct AssignExp {
+    pub target: Box<Expression>,
+    pub operator: Token,
+    pub value: Box<Expression>,
+    pub span: Span,
+}
+
+/// Integers from start to end, the end is excluded
+#[derive(Clone, Debug, PartialEq)]
+pub struct RangeExp {
+    pub start: Box<Expression>,
+    pub end: Box<Expression>,
+    pub span: Span,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct HashLiteral {
+    pub pairs: Vec<(Expression, Expression)>,
+    pub span: Span,
+}
+
 #[derive(Clone, Debug, PartialEq)]
 pub struct BlockStatement {
     pub statements: Vec<Statement>,
@@ -197,3 +389,12 @@ impl Display for BlockStatement {
         write!(f, "{s}")
     }
 }
+
+/// Joins a list of nodes separated by commas
+fn join<T: Display>(nodes: &[T]) -> String {
+    nodes
+        .iter()
+        .map(|n| n.to_string())
+        .collect::<Vec<String>>()
+        .join(", ")
+}
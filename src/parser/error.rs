@@ -0,0 +1,100 @@
+use std::fmt::Display;
+
+use crate::lexer::token::{Span, Token, TokenKind};
+
+/// Errors found while parsing a program, each one points at the token that caused it
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseError {
+    /// The token is not one of the tokens the grammar allows at this point
+    UnexpectedToken {
+        expected: Vec<TokenKind>,
+        found: Token,
+    },
+    /// No expression can start with the token
+    ExpectedExpression { found: Token },
+    /// A name was required, for instance after a let or in the parameters of a function
+    ExpectedIdentifier { found: Token },
+    /// The lexer could not make sense of the source
+    IllegalToken { found: Token },
+    /// The left side of the assignment operator is not a variable or an index
+    InvalidAssignmentTarget { found: Token },
+    /// The expression starting at the token is nested deeper than the parser allows
+    TooDeeplyNested { found: Token },
+}
+
+impl ParseError {
+    /// Stable code identifying the kind of error, it never changes between versions so tools can
+    /// rely on it
+    pub fn code(&self) -> &'static str {
+        match self {
+            ParseError::UnexpectedToken { .. } => "E0001",
+            ParseError::ExpectedExpression { .. } => "E0002",
+            ParseError::ExpectedIdentifier { .. } => "E0003",
+            ParseError::IllegalToken { .. } => "E0004",
+            ParseError::InvalidAssignmentTarget { .. } => "E0005",
+            ParseError::TooDeeplyNested { .. } => "E0006",
+        }
+    }
+
+    /// Returns the token that caused the error
+    pub fn found(&self) -> &Token {
+        match self {
+            ParseError::UnexpectedToken { found, .. }
+            | ParseError::ExpectedExpression { found }
+            | ParseError::ExpectedIdentifier { found }
+            | ParseError::IllegalToken { found }
+            | ParseError::InvalidAssignmentTarget { found }
+            | ParseError::TooDeeplyNested { found } => found,
+        }
+    }
+
+    /// Location of the offending token in the source
+    pub fn span(&self) -> &Span {
+        &self.found().span
+    }
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let found = describe(&self.found().kind);
+        match self {
+            ParseError::UnexpectedToken { expected, .. } => {
+                let expected = expected
+                    .iter()
+                    .map(|kind| format!("`{kind}`"))
+                    .collect::<Vec<String>>()
+                    .join(" or ");
+                write!(f, "expected {expected}, found {found}")
+            }
+            ParseError::ExpectedExpression { .. } => {
+                write!(f, "expected expression, found {found}")
+            }
+            ParseError::ExpectedIdentifier { .. } => {
+                write!(f, "expected identifier, found {found}")
+            }
+            ParseError::IllegalToken { found } => match &found.kind {
+                TokenKind::Illegal(err) => write!(f, "{err}"),
+                _ => write!(f, "illegal token"),
+            },
+            ParseError::InvalidAssignmentTarget { .. } => {
+                write!(f, "invalid assignment target before {found}")
+            }
+            ParseError::TooDeeplyNested { .. } => {
+                write!(f, "expression nested too deeply at {found}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Describes a token the way it is written in the source
+fn describe(kind: &TokenKind) -> String {
+    match kind {
+        TokenKind::Eof => "end of input".to_string(),
+        TokenKind::String(s) => format!("`{s:?}`"),
+        // The kind alone would only say ILLEGAL, the lexer error tells what is wrong
+        TokenKind::Illegal(err) => format!("invalid token ({err})"),
+        kind => format!("`{kind}`"),
+    }
+}
@@ -1,15 +1,17 @@
 #[cfg(test)]
 mod test {
     use crate::{
-        Lexer,
+        eval::STACK_SIZE,
+        lexer::Lexer,
         lexer::token::{Span, Token, TokenKind},
         parser::{
             self, Parser,
-            ast::{Let, Program, Statement},
+            ast::{Expression, Ident, Let, Program, Statement},
+            error::ParseError,
         },
     };
 
-    fn test_let_statement(stmt: &Statement, ident: &str) {
+    fn test_let_statement(stmt: &Statement, ident: &str, exp: &str) {
         match stmt {
             Statement::Let(ls) => {
                 // Should match a token kind ident with the given identifier
@@ -21,16 +23,18 @@ mod test {
                     "Expected ident {} found {}",
                     ident,
                     ls.identifier.kind,
-                )
+                );
+                assert_eq!(exp, ls.exp.to_string(), "Let value does not match")
             }
             _ => panic!("Not a let statement"),
         }
     }
 
-    fn test_return_statement(stmt: &Statement, ident: &str) {
+    fn test_return_statement(stmt: &Statement, exp: &str) {
         match stmt {
-            Statement::Return(_) => {
-                // TODO: Make indepth tests
+            Statement::Return(rs) => {
+                let value = rs.exp.as_ref().map(|e| e.to_string()).unwrap_or_default();
+                assert_eq!(exp, value, "Return value does not match")
             }
             _ => panic!("Not a return statement"),
         }
@@ -51,7 +55,7 @@ mod test {
         }
     }
 
-    fn parsing_errors(errs: Vec<String>) -> Program {
+    fn parsing_errors(errs: Vec<ParseError>) -> Program {
         for err in errs.iter() {
             eprintln!("Parsing error: {}", err)
         }
@@ -73,8 +77,8 @@ mod test {
     fn test_let_statements() {
         let input = r"
             let x = 5;
-            let y = 10;
-            let foobar = 838383;
+            let y = true;
+            let foobar = y
         ";
 
         let l = Lexer::new(input);
@@ -90,11 +94,11 @@ mod test {
             program.statements.len()
         );
 
-        let idents = vec!["x", "y", "foobar"];
+        let expected = vec![("x", "5"), ("y", "true"), ("foobar", "y")];
 
-        for (index, ident) in idents.into_iter().enumerate() {
+        for (index, (ident, exp)) in expected.into_iter().enumerate() {
             let stmt = program.statements.get(index).unwrap();
-            test_let_statement(stmt, ident);
+            test_let_statement(stmt, ident, exp);
         }
     }
 
@@ -102,8 +106,8 @@ mod test {
     fn test_return_statements() {
         let input = r"
             return 5;
-            return 10;
-            return 993322;
+            return;
+            return 993322 * x
         ";
 
         let l = Lexer::new(input);
@@ -124,28 +128,34 @@ mod test {
             program.statements.len()
         );
 
-        // TODO check expression matches
-        // for (index, ident) in idents.into_iter().enumerate() {
-        //     let stmt = program.statements.get(index).unwrap();
-        //     test_let_statement(stmt, ident);
-        // }
+        let expected = vec!["5", "", "(993322 * x)"];
+
+        for (index, exp) in expected.into_iter().enumerate() {
+            let stmt = program.statements.get(index).unwrap();
+            test_return_statement(stmt, exp);
+        }
     }
 
     #[test]
     fn test_string() {
-        let _p = Program {
+        let p = Program {
             statements: vec![Statement::Let(Let {
                 identifier: Token::new(
                     TokenKind::Ident {
-                        name: "x".to_string(),
+                        name: "myVar".to_string(),
                     },
-                    0,
-                    1,
+                    4,
+                    9,
                 ),
-                exp: None,
-                span: Span { start: 0, end: 1 },
+                exp: Expression::Identifier(Ident {
+                    name: "anotherVar".to_string(),
+                    span: Span { start: 12, end: 21 },
+                }),
+                span: Span { start: 0, end: 22 },
             })],
         };
+
+        assert_eq!("let myVar = anotherVar;", p.to_string());
     }
 
     #[test]
@@ -188,7 +198,7 @@ mod test {
 
     #[test]
     fn test_prefix_expressions() {
-        let inputs = [("!5;", "(!5)"), ("-15", "(-15)")];
+        let inputs = [("!5;", "(!5)"), ("-15", "(-15)"), ("~5", "(~5)")];
 
         test_parsing(&inputs);
     }
@@ -204,6 +214,16 @@ mod test {
             ("5 < 5;", "(5 < 5)"),
             ("5 == 5;", "(5 == 5)"),
             ("5 != 5;", "(5 != 5)"),
+            ("5 <= 5;", "(5 <= 5)"),
+            ("5 >= 5;", "(5 >= 5)"),
+            ("5 % 5;", "(5 % 5)"),
+            ("a && b;", "(a && b)"),
+            ("a || b;", "(a || b)"),
+            ("5 & 5;", "(5 & 5)"),
+            ("5 | 5;", "(5 | 5)"),
+            ("5 ^ 5;", "(5 ^ 5)"),
+            ("5 << 5;", "(5 << 5)"),
+            ("5 >> 5;", "(5 >> 5)"),
         ];
 
         test_parsing(&inputs);
@@ -227,15 +247,34 @@ mod test {
                 "3 + 4 * 5 == 3 * 1 + 4 * 5",
                 "((3 + (4 * 5)) == ((3 * 1) + (4 * 5)))",
             ),
-            // ("a + add(b * c) + d", "((a + add((b * c))) + d)"),
-            // (
-            //     "add(a, b, 1, 2 * 3, 4 + 5, add(6, 7 * 8))",
-            //     "add(a, b, 1, (2 * 3), (4 + 5), add(6, (7 * 8)))",
-            // ),
-            // (
-            //     "add(a + b + c * d / f + g)",
-            //     "add((((a + b) + ((c * d) / f)) + g))",
-            // ),
+            ("a + add(b * c) + d", "((a + add((b * c))) + d)"),
+            (
+                "add(a, b, 1, 2 * 3, 4 + 5, add(6, 7 * 8))",
+                "add(a, b, 1, (2 * 3), (4 + 5), add(6, (7 * 8)))",
+            ),
+            (
+                "add(a + b + c * d / f + g)",
+                "add((((a + b) + ((c * d) / f)) + g))",
+            ),
+            (
+                "a * [1, 2, 3, 4][b * c] * d",
+                "((a * ([1, 2, 3, 4][(b * c)])) * d)",
+            ),
+            (
+                "add(a * b[2], b[1], 2 * [1, 2][1])",
+                "add((a * (b[2])), (b[1]), (2 * ([1, 2][1])))",
+            ),
+            ("a + b % c", "(a + (b % c))"),
+            ("a <= b == b >= a", "((a <= b) == (b >= a))"),
+            ("a || b && c", "(a || (b && c))"),
+            ("a && b || c", "((a && b) || c)"),
+            ("a == b && c != d", "((a == b) && (c != d))"),
+            ("a & 1 == 0", "((a & 1) == 0)"),
+            ("a | b ^ c & d", "(a | (b ^ (c & d)))"),
+            ("1 << a + b", "(1 << (a + b))"),
+            ("a & b << c", "(a & (b << c))"),
+            ("a < b | c", "(a < (b | c))"),
+            ("~a & b", "((~a) & b)"),
         ];
 
         test_parsing(&inputs);
@@ -267,6 +306,18 @@ mod test {
         test_parsing(&inputs);
     }
 
+    #[test]
+    fn test_let_and_return_rendering() {
+        let inputs = [
+            ("let x = 1 + 2 * 3;", "let x = (1 + (2 * 3));"),
+            ("let y = -x", "let y = (-x);"),
+            ("return x == y;", "return (x == y);"),
+            ("return", "return;"),
+        ];
+
+        test_parsing(&inputs);
+    }
+
     #[test]
     fn test_if_expression() {
         let input = [
@@ -275,4 +326,346 @@ mod test {
         ];
         test_parsing(&input);
     }
+
+    #[test]
+    fn test_function_literal() {
+        let input = [
+            ("fn(x, y) { x + y; }", "fn(x, y) { (x + y) }"),
+            ("fn() {}", "fn() {  }"),
+            ("fn(x) { return x; }", "fn(x) { return x; }"),
+            (
+                "let add = fn(a, b) { a + b };",
+                "let add = fn(a, b) { (a + b) };",
+            ),
+        ];
+        test_parsing(&input);
+    }
+
+    #[test]
+    fn test_call_expression() {
+        let input = [
+            ("add(1, 2 * 3, 4 + 5);", "add(1, (2 * 3), (4 + 5))"),
+            ("add()", "add()"),
+            ("fn(x) { x }(5)", "fn(x) { x }(5)"),
+            ("curry(1)(2)(3)", "curry(1)(2)(3)"),
+            ("-f(x)", "(-f(x))"),
+        ];
+        test_parsing(&input);
+    }
+
+    #[test]
+    fn test_call_expression_errors() {
+        let inputs = ["add(1, 2", "fn(1) { 1 }", "fn(x, ) { x }"];
+
+        for input in inputs {
+            let mut parser = Parser::new(Lexer::new(input));
+            assert!(
+                parser.parse_program().is_err(),
+                "Expected parsing errors for: {input}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_float_literal() {
+        test_parsing(&[
+            ("3.14", "3.14"),
+            ("1e3", "1000.0"),
+            ("-2.5 * 2", "((-2.5) * 2)"),
+        ]);
+    }
+
+    #[test]
+    fn test_loops() {
+        test_parsing(&[
+            ("while (x < 10) { x; }", "while (x < 10) { x }"),
+            (
+                "while (true) { break; continue }",
+                "while true { break;continue; }",
+            ),
+            (
+                "for (x in [1, 2]) { puts(x) }",
+                "for x in [1, 2] { puts(x) }",
+            ),
+            (
+                "for (i in 0..len(a) - 1) { }",
+                "for i in (0..(len(a) - 1)) {  }",
+            ),
+            ("0..n + 1 == m", "(0..((n + 1) == m))"),
+            ("a..b || c", "(a..(b || c))"),
+        ]);
+
+        let errs = parse_errors("for (1 in a) {}; while x {}; for (x of a) {}");
+        let messages: Vec<String> = errs.iter().map(|e| e.to_string()).collect();
+        assert_eq!(
+            messages,
+            [
+                "expected identifier, found `1`",
+                "expected `(`, found `x`",
+                "expected `in`, found `of`",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_assignments() {
+        test_parsing(&[
+            ("x = 5", "(x = 5)"),
+            ("x += 1 * 2", "(x += (1 * 2))"),
+            ("a = b = c", "(a = (b = c))"),
+            ("a[i] = v", "((a[i]) = v)"),
+            (r#"h["k"][0] -= 1"#, r#"(((h["k"])[0]) -= 1)"#),
+            ("r = 0..n", "(r = (0..n))"),
+            ("x *= y /= 2", "(x *= (y /= 2))"),
+        ]);
+
+        for input in ["1 = 2", "f() = 1", "a[0:1] = b", "x + y = 1", "x = 2 %= 3"] {
+            let errs = parse_errors(input);
+            assert_eq!(errs.len(), 1, "Unexpected errors for {input}: {errs:?}");
+            assert_eq!(errs[0].code(), "E0005", "Unexpected error for {input}");
+        }
+        assert_eq!(
+            parse_errors("1 = 2")[0].to_string(),
+            "invalid assignment target before `=`"
+        );
+    }
+
+    #[test]
+    fn test_string_literal() {
+        let input = [
+            (r#""hello world";"#, r#""hello world""#),
+            (
+                r#"let s = "a\tb" + "\"c\"";"#,
+                r#"let s = ("a\tb" + "\"c\"");"#,
+            ),
+        ];
+        test_parsing(&input);
+    }
+
+    #[test]
+    fn test_array_literal() {
+        let input = [
+            ("[1, 2 * 2, 3 + 3]", "[1, (2 * 2), (3 + 3)]"),
+            ("[]", "[]"),
+            ("[[1], fn(x) { x }]", "[[1], fn(x) { x }]"),
+        ];
+        test_parsing(&input);
+    }
+
+    #[test]
+    fn test_index_and_slice_expressions() {
+        let input = [
+            ("myArray[1 + 1]", "(myArray[(1 + 1)])"),
+            ("a[-1]", "(a[(-1)])"),
+            ("a[1:3]", "(a[1:3])"),
+            ("a[:n - 1]", "(a[:(n - 1)])"),
+            ("a[1:]", "(a[1:])"),
+            ("a[:]", "(a[:])"),
+            ("f(x)[0][1:]", "((f(x)[0])[1:])"),
+        ];
+        test_parsing(&input);
+    }
+
+    #[test]
+    fn test_hash_literal() {
+        let input = [
+            (r#"{"one": 1, "two": 2}"#, r#"{"one": 1, "two": 2}"#),
+            ("{}", "{}"),
+            ("{1: 0 + 1, true: 10 - 8,}", "{1: (0 + 1), true: (10 - 8)}"),
+            ("let h = {a: [1]}; h[a]", "let h = {a: [1]};(h[a])"),
+            (
+                "if (x) { {1: 2} } else { {} }",
+                "if x { {1: 2} } else { {} }",
+            ),
+        ];
+        test_parsing(&input);
+    }
+
+    #[test]
+    fn test_hash_literal_errors() {
+        let inputs = ["{1 2}", "{1: 2 3: 4}", "{1: 2"];
+
+        for input in inputs {
+            let mut parser = Parser::new(Lexer::new(input));
+            assert!(
+                parser.parse_program().is_err(),
+                "Expected parsing errors for: {input}"
+            );
+        }
+    }
+
+    fn parse_errors(input: &str) -> Vec<ParseError> {
+        let mut parser = Parser::new(Lexer::new(input));
+        match parser.parse_program() {
+            Ok(program) => panic!("Expected parsing errors for: {input} got: {program}"),
+            Err(errs) => errs,
+        }
+    }
+
+    #[test]
+    fn test_parse_error_details() {
+        let cases = [
+            ("let = 5;", "E0003", 4, 5, "expected identifier, found `=`"),
+            ("let x 5;", "E0001", 6, 7, "expected `=`, found `5`"),
+            ("1 + ;", "E0002", 4, 5, "expected expression, found `;`"),
+            (
+                "add(1, 2",
+                "E0001",
+                8,
+                8,
+                "expected `,` or `)`, found end of input",
+            ),
+            (
+                "fn(x y) { x }",
+                "E0001",
+                5,
+                6,
+                "expected `,` or `)`, found `y`",
+            ),
+            (r#"{"a" 1}"#, "E0001", 5, 6, "expected `:`, found `1`"),
+            (
+                "let x = 99999999999999999999;",
+                "E0004",
+                8,
+                28,
+                "integer literal is too large",
+            ),
+            (
+                "let x € 5;",
+                "E0001",
+                6,
+                9,
+                "expected `=`, found invalid token (unexpected character `€`)",
+            ),
+            (
+                "if (x) { 1 } else 2",
+                "E0001",
+                18,
+                19,
+                "expected `{`, found `2`",
+            ),
+        ];
+
+        for (input, code, start, end, message) in cases {
+            let errs = parse_errors(input);
+            let err = &errs[0];
+
+            assert_eq!(err.code(), code, "Unexpected code for: {input}");
+            assert_eq!(
+                (err.span().start, err.span().end),
+                (start, end),
+                "Unexpected span for: {input}"
+            );
+            assert_eq!(err.to_string(), message, "Unexpected message for: {input}");
+        }
+    }
+
+    #[test]
+    fn test_parse_error_expected_kinds() {
+        let errs = parse_errors("[1 2]");
+
+        assert_eq!(
+            errs[0],
+            ParseError::UnexpectedToken {
+                expected: vec![TokenKind::Comma, TokenKind::RightBracket],
+                found: Token::new(TokenKind::Int(2), 3, 4),
+            }
+        );
+
+        // Errors can be handled as any other error
+        let err: Box<dyn std::error::Error> = Box::new(errs[0].clone());
+        assert_eq!(err.to_string(), "expected `,` or `]`, found `2`");
+    }
+
+    #[test]
+    fn test_error_recovery() {
+        // Each broken statement reports a single error, the rest of the program is still parsed
+        let cases = [
+            ("let x = fn(a b) { a }; let y = 2;", 1, "<error>let y = 2;"),
+            (
+                "let = 1; let y = 2; return y;",
+                1,
+                "<error>let y = 2;return y;",
+            ),
+            ("let a 1; let b 2; a + b", 2, "<error><error>(a + b)"),
+            ("1 + ; 2", 1, "<error>2"),
+            ("if (x) { let = 1; y } ; z", 1, "if x { <error>y }z"),
+        ];
+
+        for (input, errors, expected) in cases {
+            let (program, errs) = Parser::new(Lexer::new(input)).parse_program_partial();
+
+            assert_eq!(
+                errs.len(),
+                errors,
+                "Unexpected errors for {input}: {errs:?}"
+            );
+            assert_eq!(
+                program.to_string(),
+                expected,
+                "Unexpected program for {input}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_errors_inside_blocks() {
+        let errs = parse_errors("fn() { let = 1; let y 2; y }; if (a) { 1 + }");
+        let codes: Vec<&str> = errs.iter().map(|e| e.code()).collect();
+        assert_eq!(codes, ["E0003", "E0001", "E0002"]);
+
+        let (program, errs) = Parser::new(Lexer::new("if (a) { 1")).parse_program_partial();
+        assert_eq!(program.to_string(), "<error>");
+        assert_eq!(errs[0].to_string(), "expected `}`, found end of input");
+
+        let (program, _) = Parser::new(Lexer::new("let a = 1; let = 2;")).parse_program_partial();
+        let Statement::Error(span) = &program.statements[1] else {
+            panic!("Expected an error statement");
+        };
+        assert_eq!((span.start, span.end), (11, 19));
+    }
+
+    #[test]
+    fn test_nesting_limit() {
+        // Nested functions need more than the stack of the test threads before the limit
+        std::thread::Builder::new()
+            .stack_size(STACK_SIZE)
+            .spawn(check_nesting_limit)
+            .unwrap()
+            .join()
+            .unwrap();
+    }
+
+    fn check_nesting_limit() {
+        let nested = |open: &str, close: &str, depth: usize| {
+            format!("{}1{}", open.repeat(depth), close.repeat(depth))
+        };
+
+        let input = nested("(", ")", parser::MAX_NESTING - 1);
+        let program = Parser::new(Lexer::new(&input)).parse_program();
+        assert_eq!(program.unwrap().to_string(), "1");
+
+        let input = nested("", " + 1", parser::MAX_NESTING - 1);
+        assert!(Parser::new(Lexer::new(&input)).parse_program().is_ok());
+
+        let errs = parse_errors(&nested("(", ")", 20000));
+        assert_eq!(errs[0].code(), "E0006");
+        assert_eq!(errs[0].to_string(), "expression nested too deeply at `(`");
+        assert_eq!(errs[0].span().start, parser::MAX_NESTING);
+
+        for input in [
+            nested("-", "", 20000),
+            nested("[", "]", 20000),
+            nested("fn() { ", " }", 20000),
+            nested("", " + 1", 20000),
+            nested("x = ", "", 20000),
+            nested("", "()", 20000),
+            nested("", "[0]", 20000),
+            nested("while (true) { ", " }", 20000),
+            nested("for (x in y) { ", " }", 20000),
+        ] {
+            let errs = parse_errors(&input);
+            assert_eq!(errs[0].code(), "E0006", "Unexpected error for {input}");
+        }
+    }
 }
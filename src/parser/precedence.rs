@@ -1,26 +1,56 @@
 use crate::lexer::token::TokenKind;
 
+/// Binding power of the operators, from the loosest to the tightest
+/// Bitwise operators bind tighter than comparisons so `a & 1 == 0` means `(a & 1) == 0`
 #[derive(Debug, Copy, Clone, PartialEq, PartialOrd)]
 pub enum Precedence {
     Lowset,      //
+    Assign,      // x = y
+    Range,       // a..b
+    Or,          // ||
+    And,         // &&
     Equals,      // ==
     LessGreater, // < or >
+    BitOr,       // |
+    BitXor,      // ^
+    BitAnd,      // &
+    Shift,       // << or >>
     Sum,         // +
     Product,     // *
     Prefix,      // !X or -X
     Call,        // function()
+    Index,       // array[index]
 }
 
 pub fn get_token_precedence(kind: &TokenKind) -> Precedence {
     match kind {
+        TokenKind::Assign
+        | TokenKind::PlusAssign
+        | TokenKind::MinusAssign
+        | TokenKind::StarAssign
+        | TokenKind::SlashAssign
+        | TokenKind::PercentAssign => Precedence::Assign,
+        TokenKind::DotDot => Precedence::Range,
+        TokenKind::Or => Precedence::Or,
+        TokenKind::And => Precedence::And,
         TokenKind::Eq => Precedence::Equals,
         TokenKind::NotEq => Precedence::Equals,
         TokenKind::LT => Precedence::LessGreater,
         TokenKind::GT => Precedence::LessGreater,
+        TokenKind::LTEq => Precedence::LessGreater,
+        TokenKind::GTEq => Precedence::LessGreater,
+        TokenKind::BitOr => Precedence::BitOr,
+        TokenKind::BitXor => Precedence::BitXor,
+        TokenKind::BitAnd => Precedence::BitAnd,
+        TokenKind::ShiftLeft => Precedence::Shift,
+        TokenKind::ShiftRight => Precedence::Shift,
         TokenKind::Plus => Precedence::Sum,
         TokenKind::Minus => Precedence::Sum,
         TokenKind::Star => Precedence::Product,
         TokenKind::Slash => Precedence::Product,
+        TokenKind::Percent => Precedence::Product,
+        TokenKind::LeftParen => Precedence::Call,
+        TokenKind::LeftBracket => Precedence::Index,
         _ => Precedence::Lowset,
     }
 }
@@ -0,0 +1,178 @@
+use std::io::{self, BufRead, Write};
+
+use crate::{
+    diagnostics::Diagnostic,
+    eval::eval_program,
+    lexer::{Lexer, token::TokenKind},
+    object::{
+        Object,
+        environment::{Env, Environment},
+    },
+    parser::{Parser, error::ParseError},
+    source_map::SourceFile,
+};
+
+const PROMPT: &str = ">> ";
+
+const HELP: &str = r":tokens <code>  prints the tokens of the code with their span, comments included
+:ast <code>     prints the parsed program without evaluating it
+:help           prints this message
+:quit           exits the REPL";
+
+/// Read-Eval-Print loop, bindings are kept from one line to the next until the input is closed
+pub fn start<R: BufRead, W: Write>(mut input: R, mut output: W) -> io::Result<()> {
+    let env = Environment::new().into_env();
+    // Each input is its own file so diagnostics can tell which one they come from, only the
+    // current one is kept since the spans of the previous ones are never used again
+    let mut count = 0;
+
+    loop {
+        write!(output, "{PROMPT}")?;
+        output.flush()?;
+
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            // End of input
+            return Ok(());
+        }
+
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        count += 1;
+        let name = format!("<repl:{count}>");
+
+        match line.split_once(' ').unwrap_or((line, "")) {
+            (":quit", _) => return Ok(()),
+            (":help", _) => writeln!(output, "{HELP}")?,
+            (":tokens", code) => print_tokens(code, &mut output)?,
+            (":ast", code) => print_ast(&SourceFile::new(name, code), &mut output)?,
+            (command, _) if command.starts_with(':') => {
+                writeln!(output, "unknown command {command}, try :help")?
+            }
+            _ => eval_line(&SourceFile::new(name, line), &env, &mut output)?,
+        }
+    }
+}
+
+/// Parses and evaluates a line, printing its value or the errors found
+fn eval_line<W: Write>(file: &SourceFile, env: &Env, output: &mut W) -> io::Result<()> {
+    let mut parser = Parser::new(Lexer::new(file.source()));
+
+    match parser.parse_program() {
+        Ok(program) => match eval_program(&program, env) {
+            // Statements like let have no value, no need to print it
+            Object::Null => Ok(()),
+            value => writeln!(output, "{value}"),
+        },
+        Err(errs) => print_parser_errors(&errs, file, output),
+    }
+}
+
+fn print_tokens<W: Write>(code: &str, output: &mut W) -> io::Result<()> {
+    let mut lexer = Lexer::new(code).with_comments();
+
+    loop {
+        let t = lexer.next_token();
+        if t.kind == TokenKind::Eof {
+            return Ok(());
+        }
+
+        writeln!(
+            output,
+            "token: {}, literal value: {}",
+            t,
+            &code[t.span.start..t.span.end]
+        )?;
+    }
+}
+
+fn print_ast<W: Write>(file: &SourceFile, output: &mut W) -> io::Result<()> {
+    let mut parser = Parser::new(Lexer::new(file.source()));
+
+    match parser.parse_program() {
+        Ok(program) => writeln!(output, "{program}"),
+        Err(errs) => print_parser_errors(&errs, file, output),
+    }
+}
+
+fn print_parser_errors<W: Write>(
+    errs: &[ParseError],
+    file: &SourceFile,
+    output: &mut W,
+) -> io::Result<()> {
+    for err in errs {
+        write!(output, "{}", Diagnostic::from(err).render(file))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(input: &str) -> String {
+        let mut output = vec![];
+        start(input.as_bytes(), &mut output).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn keeps_bindings_between_lines() {
+        let output = run("let a = 5;\n\nlet b = a * 2;\nb + 1\n");
+        assert_eq!(output, ">> >> >> >> 11\n>> ");
+    }
+
+    #[test]
+    fn prints_errors() {
+        assert_eq!(
+            run("1 + true\n"),
+            ">> ERROR: type mismatch: INTEGER + BOOLEAN\n>> "
+        );
+        assert_eq!(
+            run("1\nlet = 1\n"),
+            ">> 1\n>> error[E0003]: expected identifier, found `=`\n \
+             --> <repl:2>:1:5\n  \
+             |\n\
+             1 | let = 1\n  \
+             |     ^ expected identifier\n>> "
+        );
+    }
+
+    #[test]
+    fn survives_deep_recursion() {
+        let input = "let n = 7;\nlet f = fn(x) { if (x == 0) { 0 } else { 1 + f(x - 1) } };\n\
+                     f(100000)\nf(n)\n";
+        // With the stack the binary gives the REPL
+        let output = std::thread::Builder::new()
+            .stack_size(crate::eval::STACK_SIZE)
+            .spawn(move || run(input))
+            .unwrap()
+            .join()
+            .unwrap();
+        assert_eq!(output, ">> >> >> ERROR: stack overflow\n>> 7\n>> ");
+    }
+
+    #[test]
+    fn meta_commands() {
+        assert_eq!(run(":ast 1 + 2 * 3\n:quit\n1\n"), ">> (1 + (2 * 3))\n>> ");
+        assert_eq!(
+            run(":tokens a;\n"),
+            ">> token: start: 0 end: 1, kind: a, literal value: a\n\
+             token: start: 1 end: 2, kind: ;, literal value: ;\n>> "
+        );
+        assert_eq!(
+            run(":tokens \"ñ\" é\n"),
+            ">> token: start: 0 end: 4, kind: ñ, literal value: \"ñ\"\n\
+             token: start: 5 end: 7, kind: é, literal value: é\n>> "
+        );
+        assert_eq!(
+            run(":tokens 1 /* one */\n"),
+            ">> token: start: 0 end: 1, kind: 1, literal value: 1\n\
+             token: start: 2 end: 11, kind: /* one */, literal value: /* one */\n>> "
+        );
+        assert_eq!(run(":nope\n"), ">> unknown command :nope, try :help\n>> ");
+    }
+}
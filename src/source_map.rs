@@ -0,0 +1,110 @@
+use std::fmt::Display;
+
+/// 1-based location in a source file, columns are counted in characters
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Display for Position {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+/// A source file with the index of its lines, used to convert the byte offsets of the spans to
+/// positions
+#[derive(Debug, Clone)]
+pub struct SourceFile {
+    name: String,
+    source: String,
+    /// Byte offset of the start of each line
+    line_starts: Vec<usize>,
+}
+
+impl SourceFile {
+    pub fn new(name: impl Into<String>, source: impl Into<String>) -> Self {
+        let source = source.into();
+        let line_starts = std::iter::once(0)
+            .chain(source.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+
+        Self {
+            name: name.into(),
+            source,
+            line_starts,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// Converts a byte offset to a position, offsets past the end are clamped to the end
+    pub fn position(&self, offset: usize) -> Position {
+        let offset = offset.min(self.source.len());
+        let line = self.line_starts.partition_point(|&start| start <= offset);
+        let line_start = self.line_starts[line - 1];
+
+        let column = self.source[line_start..]
+            .char_indices()
+            .take_while(|(i, _)| line_start + i < offset)
+            .count();
+
+        Position {
+            line,
+            column: column + 1,
+        }
+    }
+
+    /// Returns the text of the 1-based line without its line break
+    pub fn line_text(&self, line: usize) -> &str {
+        let start = self.line_starts[line - 1];
+        let end = self
+            .line_starts
+            .get(line)
+            .map(|&next| next - 1)
+            .unwrap_or(self.source.len());
+
+        self.source[start..end].trim_end_matches('\r')
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn positions() {
+        let file = SourceFile::new("main.mk", "let a = 1;\r\n\nlet ñb = 2;");
+
+        let cases = [
+            (0, 1, 1),
+            (4, 1, 5),
+            (10, 1, 11),
+            (12, 2, 1),
+            (13, 3, 1),
+            (17, 3, 5),
+            // The ñ takes two bytes but a single column
+            (19, 3, 6),
+            (100, 3, 12),
+        ];
+
+        for (offset, line, column) in cases {
+            assert_eq!(
+                file.position(offset),
+                Position { line, column },
+                "offset: {offset}"
+            );
+        }
+
+        assert_eq!(file.line_text(1), "let a = 1;");
+        assert_eq!(file.line_text(2), "");
+        assert_eq!(file.line_text(3), "let ñb = 2;");
+    }
+}
@@ -0,0 +1,533 @@
+pub mod error;
+mod vm_test;
+
+use std::{cell::RefCell, rc::Rc};
+
+use error::RuntimeError;
+
+use crate::{
+    compiler::{
+        Bytecode,
+        code::{Opcode, SLICE_END, SLICE_START, read_u16, span_at},
+    },
+    eval::{
+        eval_index_expression, eval_infix_expression, eval_prefix_expression, index_path,
+        into_iterable, new_range, range_bound, set_path, slice_array, slice_bound, slice_elements,
+    },
+    lexer::token::{Span, TokenKind},
+    object::{Capture, Closure, Hash, Object, Variable, builtins::Builtins},
+};
+
+/// Largest number of nested calls
+pub const MAX_FRAMES: usize = 1024;
+/// Largest number of temporary values on the stack, it leaves room for the temporaries of every
+/// frame so deep recursion runs out of frames first
+pub const STACK_SIZE: usize = 16 * MAX_FRAMES;
+
+/// Why an instruction failed, the span is added when the error leaves the vm
+enum Failure {
+    Error(String),
+    StackOverflow,
+}
+
+/// A call being run
+struct Frame {
+    closure: Rc<Closure>,
+    /// Offset of the next instruction
+    ip: usize,
+    /// Where the temporaries of the call start on the stack, the function is just below them
+    base_pointer: usize,
+    /// Variables of the call, the arguments are the first ones and the other ones are set by
+    /// their lets
+    locals: Vec<Variable>,
+}
+
+/// Stack machine running the bytecode of the compiler, it gives the same results as the
+/// evaluator
+pub struct Vm {
+    constants: Vec<Object>,
+    builtins: Builtins,
+    stack: Vec<Object>,
+    /// Globals by slot, the ones that are not set are looked up in the builtins
+    globals: Vec<Option<Object>>,
+    /// Names of the globals, for the builtins and the errors
+    names: Vec<String>,
+    frames: Vec<Frame>,
+    /// Offset of the instruction being run, used to find its span
+    offset: usize,
+}
+
+impl Vm {
+    pub fn new(bytecode: Bytecode) -> Self {
+        let locals = new_variables(bytecode.main.num_locals);
+        let main = Closure {
+            function: bytecode.main,
+            free: vec![],
+        };
+
+        Self {
+            constants: bytecode.constants,
+            builtins: Builtins::default(),
+            stack: vec![],
+            globals: vec![None; bytecode.globals.len()],
+            names: bytecode.globals,
+            frames: vec![Frame {
+                closure: Rc::new(main),
+                ip: 0,
+                base_pointer: 0,
+                locals,
+            }],
+            offset: 0,
+        }
+    }
+
+    /// Runs the program until its main function returns, returns the value it returned
+    pub fn run(mut self) -> Result<Object, RuntimeError> {
+        loop {
+            match self.step() {
+                Ok(Some(value)) => return Ok(value),
+                Ok(None) => {}
+                Err(failure) => {
+                    let span = self.current_span();
+                    return Err(match failure {
+                        Failure::Error(message) => RuntimeError::Failed { message, span },
+                        Failure::StackOverflow => RuntimeError::StackOverflow { span },
+                    });
+                }
+            }
+        }
+    }
+
+    /// Runs the next instruction, returns the value of the program once main returns
+    fn step(&mut self) -> Result<Option<Object>, Failure> {
+        let frame = self
+            .frames
+            .last_mut()
+            .expect("The vm stops when main returns");
+        let instructions = &frame.closure.function.instructions;
+        self.offset = frame.ip;
+
+        let op = instructions
+            .get(frame.ip)
+            .and_then(|byte| Opcode::from_byte(*byte))
+            .ok_or_else(|| Failure::Error(format!("invalid instruction at {}", frame.ip)))?;
+
+        let mut operands = [0; 2];
+        let mut next = frame.ip + 1;
+        for (operand, width) in operands.iter_mut().zip(op.definition().operand_widths) {
+            let Some(bytes) = instructions.get(next..next + width) else {
+                return Err(Failure::Error(format!("truncated {op} at {}", frame.ip)));
+            };
+            *operand = match width {
+                2 => read_u16(bytes, 0),
+                _ => bytes[0] as usize,
+            };
+            next += width;
+        }
+        frame.ip = next;
+
+        match op {
+            Opcode::Constant => self.push(self.constants[operands[0]].clone())?,
+            Opcode::Pop => {
+                self.pop();
+            }
+            Opcode::Dup => {
+                let top = self.pop();
+                self.push(top.clone())?;
+                self.push(top)?;
+            }
+            Opcode::True => self.push(Object::Boolean(true))?,
+            Opcode::False => self.push(Object::Boolean(false))?,
+            Opcode::Null => self.push(Object::Null)?,
+
+            Opcode::Add
+            | Opcode::Sub
+            | Opcode::Mul
+            | Opcode::Div
+            | Opcode::Mod
+            | Opcode::BitAnd
+            | Opcode::BitOr
+            | Opcode::BitXor
+            | Opcode::ShiftLeft
+            | Opcode::ShiftRight
+            | Opcode::Equal
+            | Opcode::NotEqual
+            | Opcode::LessThan
+            | Opcode::LessEqual
+            | Opcode::GreaterThan
+            | Opcode::GreaterEqual => {
+                let right = self.pop();
+                let left = self.pop();
+                self.push_result(eval_infix_expression(&infix_operator(op), left, right))?;
+            }
+            Opcode::Minus | Opcode::Bang | Opcode::BitNot => {
+                let operator = match op {
+                    Opcode::Minus => TokenKind::Minus,
+                    Opcode::Bang => TokenKind::Bang,
+                    _ => TokenKind::Tilde,
+                };
+                let right = self.pop();
+                self.push_result(eval_prefix_expression(&operator, right))?;
+            }
+
+            Opcode::Jump => self.frame_mut().ip = operands[0],
+            Opcode::JumpNotTruthy => {
+                if !self.pop().is_truthy() {
+                    self.frame_mut().ip = operands[0];
+                }
+            }
+
+            Opcode::GetGlobal => {
+                let value = match &self.globals[operands[0]] {
+                    Some(value) => value.clone(),
+                    None => self.builtin(operands[0])?,
+                };
+                self.push(value)?;
+            }
+            Opcode::SetGlobal => self.globals[operands[0]] = Some(self.pop()),
+            Opcode::AssignGlobal => {
+                let value = self.pop();
+                self.declared_global(operands[0])?;
+                self.globals[operands[0]] = Some(value);
+            }
+            Opcode::CheckGlobal => {
+                let value = self.declared_global(operands[0])?;
+                self.push(value)?;
+            }
+            Opcode::GetLocal | Opcode::GetFree => {
+                let value = self.variable(op, operands[0]).borrow().clone();
+                let value = value.ok_or_else(|| unset_variable(operands[0]))?;
+                self.push(value)?;
+            }
+            Opcode::SetLocal | Opcode::SetFree => {
+                let value = self.pop();
+                *self.variable(op, operands[0]).borrow_mut() = Some(value);
+            }
+            Opcode::TryGetLocal | Opcode::TryGetFree => {
+                let value = self.variable(op, operands[0]).borrow().clone();
+                if let Some(value) = value {
+                    self.push(value)?;
+                    self.frame_mut().ip = operands[1];
+                }
+            }
+            Opcode::TrySetLocal | Opcode::TrySetFree => {
+                let variable = self.variable(op, operands[0]).clone();
+                if variable.borrow().is_some() {
+                    *variable.borrow_mut() = Some(self.pop());
+                    self.frame_mut().ip = operands[1];
+                }
+            }
+            Opcode::NewLocals => {
+                let locals = &mut self.frame_mut().locals[operands[0]..=operands[1]];
+                for local in locals.iter_mut() {
+                    *local = Rc::new(RefCell::new(None));
+                }
+            }
+
+            Opcode::Array => {
+                let elements = self.stack.split_off(self.stack.len() - operands[0]);
+                self.push(Object::Array(Rc::new(elements)))?;
+            }
+            Opcode::Hash => {
+                let items = self.stack.split_off(self.stack.len() - 2 * operands[0]);
+                let mut hash = Hash::new();
+                for pair in items.chunks(2) {
+                    let Some(key) = pair[0].hash_key() else {
+                        return Err(Failure::Error(format!(
+                            "unusable as hash key: {}",
+                            pair[0].type_name()
+                        )));
+                    };
+                    hash.insert(key, pair[1].clone());
+                }
+                self.push(Object::Hash(Rc::new(hash)))?;
+            }
+            Opcode::Index => {
+                let index = self.pop();
+                let left = self.pop();
+                self.push_result(eval_index_expression(left, index))?;
+            }
+            Opcode::CheckSliceable => {
+                if let Err(err) = slice_elements(self.top()) {
+                    return Err(failure(err));
+                }
+            }
+            Opcode::CheckSliceBound => {
+                if let Err(err) = slice_bound(self.top()) {
+                    return Err(failure(err));
+                }
+            }
+            Opcode::Slice => {
+                let flags = operands[0];
+                let end = (flags & SLICE_END != 0).then(|| self.pop());
+                let start = (flags & SLICE_START != 0).then(|| self.pop());
+                let left = self.pop();
+                self.push_result(slice_array(left, [start, end]))?;
+            }
+            Opcode::IndexPath => {
+                let container = self.pop();
+                let indexes = &self.stack[self.stack.len() - operands[0]..];
+                let value = index_path(container, indexes);
+                self.push_result(value)?;
+            }
+            Opcode::SetPath => {
+                let container = self.pop();
+                let value = self.pop();
+                let indexes = self.stack.split_off(self.stack.len() - operands[0]);
+                match set_path(container, &indexes, value.clone()) {
+                    Ok(updated) => {
+                        self.push(value)?;
+                        self.push(updated)?;
+                    }
+                    Err(err) => return Err(failure(err)),
+                }
+            }
+            Opcode::CheckRangeBound => {
+                if let Err(err) = range_bound(self.top()) {
+                    return Err(failure(err));
+                }
+            }
+            Opcode::Range => {
+                let end = self.pop();
+                let start = self.pop();
+                self.push_result(new_range(start, end))?;
+            }
+
+            Opcode::Call => self.call(operands[0])?,
+            Opcode::ReturnValue => {
+                let value = self.pop();
+                return self.return_from_call(value);
+            }
+            Opcode::Return => return self.return_from_call(Object::Null),
+            Opcode::Fail => return Err(failure(self.constants[operands[0]].clone())),
+            Opcode::Closure => {
+                let Object::CompiledFunction(function) = &self.constants[operands[0]] else {
+                    return Err(Failure::Error(format!(
+                        "not a function: {}",
+                        self.constants[operands[0]].type_name()
+                    )));
+                };
+                let function = function.clone();
+                let frame = self.frame();
+                let free = function
+                    .captures
+                    .iter()
+                    .map(|capture| match *capture {
+                        Capture::Local(slot) => frame.locals[slot].clone(),
+                        Capture::Free(index) => frame.closure.free[index].clone(),
+                    })
+                    .collect();
+                self.push(Object::Closure(Rc::new(Closure { function, free })))?;
+            }
+
+            Opcode::IterStart => {
+                let iterable = into_iterable(self.pop());
+                if iterable.is_error() {
+                    return Err(failure(iterable));
+                }
+                self.push(iterable)?;
+                self.push(Object::Integer(0))?;
+            }
+            Opcode::IterNext => {
+                let top = self.stack.len() - 1;
+                let Object::Integer(position) = self.stack[top] else {
+                    return Err(Failure::Error("iteration position lost".to_string()));
+                };
+                let item = match &self.stack[top - 1] {
+                    Object::Array(elements) => elements.get(position as usize).cloned(),
+                    Object::Range(start, end) => start
+                        .checked_add(position)
+                        .filter(|value| value < end)
+                        .map(Object::Integer),
+                    _ => None,
+                };
+
+                match item {
+                    Some(item) => {
+                        self.stack[top] = Object::Integer(position + 1);
+                        self.push(item)?;
+                    }
+                    None => self.frame_mut().ip = operands[0],
+                }
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Calls the function below the arguments on top of the stack
+    fn call(&mut self, num_args: usize) -> Result<(), Failure> {
+        let callee = self.stack.len() - 1 - num_args;
+
+        match self.stack[callee].clone() {
+            Object::Closure(closure) => {
+                let function = &closure.function;
+                if function.num_parameters != num_args {
+                    return Err(Failure::Error(format!(
+                        "wrong number of arguments: want={}, got={}",
+                        function.num_parameters, num_args
+                    )));
+                }
+
+                if self.frames.len() >= MAX_FRAMES {
+                    return Err(Failure::StackOverflow);
+                }
+
+                // The arguments are the first locals, the other ones are not set yet
+                let args = self.stack.split_off(callee + 1);
+                let mut locals = new_variables(function.num_locals);
+                for (local, arg) in locals.iter_mut().zip(args) {
+                    *local = Rc::new(RefCell::new(Some(arg)));
+                }
+                self.frames.push(Frame {
+                    closure,
+                    ip: 0,
+                    base_pointer: callee + 1,
+                    locals,
+                });
+                Ok(())
+            }
+            Object::Builtin(builtin) => {
+                let args = self.stack.split_off(callee + 1);
+                self.pop();
+                match builtin.call(&args) {
+                    Ok(value) => self.push(value),
+                    Err(err) => Err(Failure::Error(err.to_string())),
+                }
+            }
+            obj => Err(Failure::Error(format!(
+                "not a function: {}",
+                obj.type_name()
+            ))),
+        }
+    }
+
+    /// Leaves the current call, returning from main ends the program
+    fn return_from_call(&mut self, value: Object) -> Result<Option<Object>, Failure> {
+        let frame = self.frames.pop().expect("The vm stops when main returns");
+        if self.frames.is_empty() {
+            return Ok(Some(value));
+        }
+
+        // Drops the temporaries and the function itself
+        self.stack.truncate(frame.base_pointer - 1);
+        self.push(value)?;
+        Ok(None)
+    }
+
+    /// Builtin named like the global, when the global is not set
+    fn builtin(&self, global: usize) -> Result<Object, Failure> {
+        let name = &self.names[global];
+        match self.builtins.get(name) {
+            Some(builtin) => Ok(Object::Builtin(builtin.clone())),
+            None => Err(Failure::Error(format!("identifier not found: {name}"))),
+        }
+    }
+
+    /// Value of a global that is about to be assigned, it must have been set by a let
+    fn declared_global(&self, global: usize) -> Result<Object, Failure> {
+        self.globals[global].clone().ok_or_else(|| {
+            Failure::Error(format!(
+                "assignment to undeclared variable: {}",
+                self.names[global]
+            ))
+        })
+    }
+
+    /// Local or free variable of the current call an instruction refers to
+    fn variable(&self, op: Opcode, index: usize) -> &Variable {
+        let frame = self.frame();
+        match op {
+            Opcode::GetLocal | Opcode::SetLocal | Opcode::TryGetLocal | Opcode::TrySetLocal => {
+                &frame.locals[index]
+            }
+            _ => &frame.closure.free[index],
+        }
+    }
+
+    fn push(&mut self, obj: Object) -> Result<(), Failure> {
+        if self.stack.len() >= STACK_SIZE {
+            return Err(Failure::StackOverflow);
+        }
+        self.stack.push(obj);
+        Ok(())
+    }
+
+    /// Pushes the result of an operation of the evaluator, errors stop the vm
+    fn push_result(&mut self, obj: Object) -> Result<(), Failure> {
+        if obj.is_error() {
+            return Err(failure(obj));
+        }
+        self.push(obj)
+    }
+
+    fn pop(&mut self) -> Object {
+        self.stack
+            .pop()
+            .expect("The compiler and the loader keep the stack balanced")
+    }
+
+    fn top(&self) -> &Object {
+        self.stack
+            .last()
+            .expect("The compiler and the loader keep the stack balanced")
+    }
+
+    fn frame(&self) -> &Frame {
+        self.frames.last().expect("The vm stops when main returns")
+    }
+
+    fn frame_mut(&mut self) -> &mut Frame {
+        self.frames
+            .last_mut()
+            .expect("The vm stops when main returns")
+    }
+
+    /// Span of the instruction being run
+    fn current_span(&self) -> Span {
+        self.frames
+            .last()
+            .and_then(|frame| span_at(&frame.closure.function.spans, self.offset))
+            .cloned()
+            .unwrap_or(Span { start: 0, end: 0 })
+    }
+}
+
+/// Variables that are not set yet
+fn new_variables(count: usize) -> Vec<Variable> {
+    (0..count).map(|_| Rc::new(RefCell::new(None))).collect()
+}
+
+/// The compiler only reads the variables it knows are set without trying them first
+fn unset_variable(index: usize) -> Failure {
+    Failure::Error(format!("variable {index} read before it is set"))
+}
+
+fn failure(err: Object) -> Failure {
+    match err {
+        Object::Error(message) => Failure::Error(message),
+        obj => Failure::Error(obj.to_string()),
+    }
+}
+
+/// Operator of the evaluator the instruction runs
+fn infix_operator(op: Opcode) -> TokenKind {
+    match op {
+        Opcode::Add => TokenKind::Plus,
+        Opcode::Sub => TokenKind::Minus,
+        Opcode::Mul => TokenKind::Star,
+        Opcode::Div => TokenKind::Slash,
+        Opcode::Mod => TokenKind::Percent,
+        Opcode::BitAnd => TokenKind::BitAnd,
+        Opcode::BitOr => TokenKind::BitOr,
+        Opcode::BitXor => TokenKind::BitXor,
+        Opcode::ShiftLeft => TokenKind::ShiftLeft,
+        Opcode::ShiftRight => TokenKind::ShiftRight,
+        Opcode::Equal => TokenKind::Eq,
+        Opcode::NotEqual => TokenKind::NotEq,
+        Opcode::LessThan => TokenKind::LT,
+        Opcode::LessEqual => TokenKind::LTEq,
+        Opcode::GreaterThan => TokenKind::GT,
+        _ => TokenKind::GTEq,
+    }
+}
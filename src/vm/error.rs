@@ -0,0 +1,31 @@
+use std::fmt::Display;
+
+use crate::lexer::token::Span;
+
+/// Errors that stop the vm, they point at the code of the instruction that failed
+#[derive(Debug, Clone, PartialEq)]
+pub enum RuntimeError {
+    /// An operation failed, the message is the one the evaluator gives for the same error
+    Failed { message: String, span: Span },
+    /// The calls are nested too deeply or the stack is full
+    StackOverflow { span: Span },
+}
+
+impl RuntimeError {
+    pub fn span(&self) -> &Span {
+        match self {
+            RuntimeError::Failed { span, .. } | RuntimeError::StackOverflow { span } => span,
+        }
+    }
+}
+
+impl Display for RuntimeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RuntimeError::Failed { message, .. } => write!(f, "{message}"),
+            RuntimeError::StackOverflow { .. } => write!(f, "stack overflow"),
+        }
+    }
+}
+
+impl std::error::Error for RuntimeError {}
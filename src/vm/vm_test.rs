@@ -0,0 +1,322 @@
+#[cfg(test)]
+mod test {
+    use std::rc::Rc;
+
+    use crate::{
+        compiler::Compiler,
+        eval::eval_program,
+        lexer::{Lexer, token::Span},
+        mkc::CompiledScript,
+        object::{Object, environment::Environment},
+        parser::{Parser, ast::Program},
+        source_map::SourceFile,
+        vm::{Vm, error::RuntimeError},
+    };
+
+    /// Programs run by both the vm and the evaluator, they must give the same result
+    const CORPUS: &[&str] = &[
+        // Arithmetic
+        "5",
+        "-5 + 10 * 2",
+        "(5 + 10 * 2 + 15 / 3) * 2 + -10",
+        "-7 / 2",
+        "7 % -3",
+        "1 / 0",
+        "5 % 0",
+        "9223372036854775807 + 1",
+        "1 << 3 | 1",
+        "~5 ^ 3 & 6",
+        "-8 >> 1",
+        "1 << 64",
+        "2.5 * 2",
+        "1 / 2.0",
+        "1.0 / 0",
+        "0.1 + 0.2 == 0.3",
+        "int(2.75) + float(\"1.5\")",
+        // Booleans and comparisons
+        "true == !false",
+        "1 < 2 == true",
+        "2 < 2.5",
+        "1 <= 1 && 2 >= 3",
+        "false || !true",
+        "0 && 1",
+        "null_value || true",
+        "1 == true",
+        "true + false",
+        "-true",
+        "~1.5",
+        "!!5",
+        // Strings
+        "\"Hello\" + \" \" + \"World\"",
+        "\"a\" == \"a\"",
+        "\"a\" - \"b\"",
+        // Conditionals
+        "if (1 > 2) { 10 } else { 20 }",
+        "if (false) { 10 }",
+        "if (true) { let a = 1; }",
+        "if (true) { }",
+        "if (1) { if (2) { 3 } }",
+        // Bindings
+        "let a = 5; let b = a * 2; a + b",
+        "let a = 1; let a = a + 1; a",
+        "let a = 1;",
+        "1; let a = 2;",
+        "foo",
+        // Collections
+        "[1, 2 * 2, 3 + 3][1]",
+        "[1, 2, 3][-1]",
+        "[1, 2, 3][3]",
+        "[1, 2][\"a\"]",
+        "[1, 2, 3, 4][1:3]",
+        "[1, 2, 3][:-1]",
+        "[1, 2, 3][2:1]",
+        "[1][true:]",
+        "5[0]",
+        "5[1:]",
+        "true[1 / 0:]",
+        "[1][true:1 / 0]",
+        "[1, 2, 3][first([]):]",
+        "[1, 2, 3][:last([])]",
+        "[1, 2, 3][:]",
+        "[1][1 / 0:true]",
+        "{\"one\": 1, \"two\": 2}[\"two\"]",
+        "{1: true}[2]",
+        "{[1]: 2}",
+        "{fn() {}: 1}",
+        "{1: 2}[[]]",
+        "[1] == [1]",
+        "1..3",
+        "(0..3)[1]",
+        "-(1..2)",
+        "1..\"a\"",
+        "true..(1 / 0)",
+        "(1 / 0)..true",
+        // Assignments
+        "let h = {\"a\": 1}; h[\"b\"] = 2; h",
+        "let a = [1, 2]; a[0] += 10; a",
+        "let a = [[1], [2]]; a[1][0] = 5; a",
+        "let a = [1, 2]; let b = a; b[0] = 9; [a, b]",
+        "let a = [1]; a[5] = 1",
+        "let a = [1]; a[\"x\"] = 1",
+        "let h = {}; h[[]] = 1",
+        "let s = \"ab\"; s[0] = \"c\"",
+        "x = 5",
+        "x += 5",
+        "let x = 1; x = x + 1; x *= 3; x",
+        "let a = 1; let b = a = 5; [a, b]",
+        "let x = 1; x /= 0",
+        "let n = 0; let f = fn() { n += 1; n }; let a = [0, 0, 0, 0]; a[f()] += 10; [a, n]",
+        "let log = []; let f = fn(x) { log = push(log, x); x }; let m = [[0, 0], [0, 0]]; m[f(1)][f(0)] = f(5); [m, log]",
+        "let a = [[1]]; a[0][1] += 1",
+        "let a = [1]; a[0][0] = 1",
+        // Functions
+        "let add = fn(a, b) { a + b }; add(1, 2)",
+        "let f = fn() { return 1; 2 }; f()",
+        "let f = fn() { }; f()",
+        "let f = fn() { 1; let a = 2; }; f()",
+        "fn(x) { x }(5)",
+        "fn(x, x) { x }(1, 2)",
+        "fn(x, y, x) { [x, y] }(1, 2, 3)",
+        "let f = fn(a) { a }; f()",
+        "5()",
+        "if (true) { return 5; } 10",
+        "return;",
+        "let f = fn(x) { let y = x * 2; let z = y + 1; z }; f(3) + f(4)",
+        "let a = 10; let f = fn() { a = a + 1; a }; f(); f()",
+        // Builtins
+        "len(\"héllo\")",
+        "len([1, 2]) + len({1: 2})",
+        "len(1)",
+        "len()",
+        "first([1, 2])",
+        "last([])",
+        "rest([])",
+        "rest([1, 2, 3])",
+        "push([1], 2)",
+        "type(fn() {})",
+        "type(len)",
+        "type(1..2)",
+        "str([1, \"a\"])",
+        "int(\"x\")",
+        "let len = fn(x) { 42 }; len([])",
+        // Closures
+        "let newAdder = fn(a) { fn(b) { a + b } }; newAdder(2)(3)",
+        "let f = fn(a) { let g = fn(b) { let h = fn(c) { a + b + c }; h }; g }; f(1)(2)(3)",
+        "let f = fn(a) { let b = a * 2; fn() { [a, b] } }; f(5)()",
+        // Recursion
+        "let fib = fn(n) { if (n < 2) { n } else { fib(n - 1) + fib(n - 2) } }; fib(15)",
+        "let outer = fn() { let countDown = fn(x) { if (x == 0) { return 0; } countDown(x - 1) }; countDown(5) }; outer()",
+        "let map = fn(arr, f) { let result = []; for (x in arr) { result = push(result, f(x)); } result }; map([1, 2, 3], fn(x) { x * 2 })",
+        "let reduce = fn(arr, acc, f) { if (len(arr) == 0) { acc } else { reduce(rest(arr), f(acc, first(arr)), f) } }; reduce([1, 2, 3, 4], 0, fn(a, b) { a + b })",
+        // Scopes
+        "let f = fn() { g() }; let g = fn() { 7 }; f()",
+        "let f = fn() { x }; f()",
+        "let f = fn() { len([1]) }; f()",
+        "let f = fn() { len([]) }; let len = fn(x) { 42 }; f()",
+        "let f = fn() { f }; let g = f; let f = 1; g()",
+        "let isEven = fn(n) { if (n == 0) { true } else { isOdd(n - 1) } }; let isOdd = fn(n) { if (n == 0) { false } else { isEven(n - 1) } }; [isEven(10), isOdd(7)]",
+        "let f = fn() { let even = fn(n) { if (n == 0) { true } else { odd(n - 1) } }; let odd = fn(n) { if (n == 0) { false } else { even(n - 1) } }; odd(7) }; f()",
+        "let f = fn() { let a = 1; let g = fn() { a }; a = 2; g() }; f()",
+        "let counter = fn() { let n = 0; fn() { n += 1; n } }; let c = counter(); c(); c(); c()",
+        "let f = fn(a) { let g = fn() { a = a * 2 }; g(); a }; f(4)",
+        "let x = 1; let f = fn() { let y = x; let x = 2; [x, y] }; f()",
+        "let f = fn() { if (false) { let z = 1; } z }; f()",
+        "let f = fn(c) { if (c) { let z = 1; } z }; f(true)",
+        "x = y",
+        "if (false) { y = 1 }; 5",
+        "let f = fn() { y = 1 }; f()",
+        // Loops
+        "let i = 0; let sum = 0; while (i < 10) { i += 1; if (i % 2 == 0) { continue; } sum += i; } sum",
+        "let n = 0; while (true) { n += 1; if (n == 5) { break; } } n",
+        "while (false) { }",
+        "let sum = 0; for (i in 0..5) { sum += i; } sum",
+        "let s = \"\"; for (c in \"abc\") { s = c + s; } s",
+        "let keys = []; for (k in {\"a\": 1, \"b\": 2}) { keys = push(keys, k); } keys",
+        "let n = 0; for (x in [[1], [2, 3]]) { n += len(x); } n",
+        "for (x in 5) { }",
+        "for (x in []) { x }",
+        "for (x in 3..0) { x }",
+        "let total = 0; for (i in 0..3) { for (j in 0..3) { if (j > i) { break; } total += 1; } } total",
+        "let f = fn() { for (i in 0..10) { if (i == 3) { return i; } } }; f()",
+        "let f = fn() { let fs = []; for (i in 0..3) { fs = push(fs, fn() { i * 10 }); } fs }; let fs = f(); fs[0]() + fs[2]()",
+        "let count = 0; let i = 0; while (i < 3) { for (j in 0..10) { if (j == 2) { break; } count += 1; } i += 1; } count",
+        "break;",
+        "if (false) { break; } 5",
+        "let f = fn(x) { if (x) { break; } 1 }; [f(false), f(true)]",
+        "for (i in 0..3) { let f = fn() { break; }; f() }",
+        "let fs = []; for (i in 0..3) { fs = push(fs, fn() { i }); } [fs[0](), fs[2]()]",
+        "let fs = []; for (i in 0..3) { let g = fn() { j }; let j = i * 2; fs = push(fs, g); } [fs[0](), fs[2]()]",
+        "let i = 0; let fs = []; while (i < 2) { fs = push(fs, fn() { i }); i += 1; } fs[0]()",
+        "let x = 5; for (i in 0..2) { let x = i; } x",
+        "let x = 5; for (i in 0..2) { x = i; } x",
+        "let total = 0; for (i in 0..3) { let sq = i * i; total += sq; } total",
+        "for (i in 0..2) { let j = i; } j",
+        "let f = fn() { for (i in 0..3) { let n = 0; n += i; if (i == 2) { return n; } } }; f()",
+    ];
+
+    // Panics if the program cannot be parsed
+    fn parse(input: &str) -> Program {
+        match Parser::new(Lexer::new(input)).parse_program() {
+            Ok(program) => program,
+            Err(errs) => {
+                for err in errs.iter() {
+                    eprintln!("Parsing error: {}", err)
+                }
+                panic!("Parsing exited with errors")
+            }
+        }
+    }
+
+    fn run(input: &str) -> Result<Object, RuntimeError> {
+        let bytecode = match Compiler::new().compile(&parse(input)) {
+            Ok(bytecode) => bytecode,
+            Err(err) => panic!("Could not compile {input}: {err}"),
+        };
+        Vm::new(bytecode).run()
+    }
+
+    /// Compile and runtime errors become error objects, like the evaluator reports them
+    fn run_as_object(input: &str) -> Object {
+        match Compiler::new().compile(&parse(input)) {
+            Ok(bytecode) => Vm::new(bytecode)
+                .run()
+                .unwrap_or_else(|err| Object::Error(err.to_string())),
+            Err(err) => Object::Error(err.to_string()),
+        }
+    }
+
+    #[test]
+    fn matches_the_evaluator() {
+        for input in CORPUS {
+            let expected = eval_program(&parse(input), &Environment::new().into_env());
+            assert_eq!(
+                run_as_object(input),
+                expected,
+                "The vm and the evaluator disagree on: {input}"
+            );
+        }
+    }
+
+    #[test]
+    fn compiled_code_passes_validation() {
+        for input in CORPUS {
+            let Ok(bytecode) = Compiler::new().compile(&parse(input)) else {
+                continue;
+            };
+            let script = CompiledScript::new(&SourceFile::new("test.mk", *input), bytecode);
+            let loaded = CompiledScript::from_bytes(&script.to_bytes());
+            assert_eq!(
+                loaded,
+                Ok(script),
+                "The loader rejects the code of: {input}"
+            );
+        }
+    }
+
+    #[test]
+    fn run_programs() {
+        let cases = [
+            ("1 + 2 * 3", Object::Integer(7)),
+            (
+                "let fib = fn(n) { if (n < 2) { n } else { fib(n - 1) + fib(n - 2) } }; fib(20)",
+                Object::Integer(6765),
+            ),
+            (
+                "let a = []; for (i in 0..3) { a = push(a, i); } a",
+                Object::Array(Rc::new(vec![
+                    Object::Integer(0),
+                    Object::Integer(1),
+                    Object::Integer(2),
+                ])),
+            ),
+            (
+                "let newAdder = fn(a) { fn(b) { a + b } }; let addTwo = newAdder(2); addTwo(3)",
+                Object::Integer(5),
+            ),
+            // The 1 left on the stack by the addition is dropped by the break
+            (
+                "let n = 0; while (true) { n += 1; 1 + if (n > 2) { break; } else { 0 }; } n",
+                Object::Integer(3),
+            ),
+        ];
+
+        for (input, expected) in cases {
+            assert_eq!(run(input), Ok(expected), "Unexpected result for: {input}");
+        }
+    }
+
+    #[test]
+    fn runtime_errors() {
+        assert_eq!(
+            run("let a = 1;\na + true"),
+            Err(RuntimeError::Failed {
+                message: "type mismatch: INTEGER + BOOLEAN".to_string(),
+                span: Span { start: 11, end: 19 },
+            })
+        );
+
+        // The span is the one of the code of the function that failed
+        assert_eq!(
+            run("let f = fn(x) { x / 0 };\nf(1)"),
+            Err(RuntimeError::Failed {
+                message: "division by zero".to_string(),
+                span: Span { start: 16, end: 21 },
+            })
+        );
+    }
+
+    #[test]
+    fn stack_overflow() {
+        let input = "let f = fn(x) { f(x + 1) };\nf(0)";
+        assert_eq!(
+            run(input),
+            Err(RuntimeError::StackOverflow {
+                span: Span { start: 16, end: 24 },
+            })
+        );
+
+        // Deep recursion that does not overflow still works
+        let input = "let sum = fn(n) { if (n == 0) { 0 } else { n + sum(n - 1) } }; sum(500)";
+        assert_eq!(run(input), Ok(Object::Integer(125250)));
+    }
+}